@@ -0,0 +1,162 @@
+use crate::bucket::log::parse_date;
+use crate::bucket::tree::{object_path, ObjectType, TreeEntry, MODE_DIR};
+use chrono::NaiveDateTime;
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::File,
+    io::{BufWriter, Error, Write},
+    path::{Path, PathBuf},
+};
+use zip::{write::SimpleFileOptions, CompressionMethod, DateTime, ZipWriter};
+
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    Zip,
+}
+
+impl ArchiveFormat {
+    pub fn parse(format: &str) -> Option<ArchiveFormat> {
+        match format {
+            "tar" => Some(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" => Some(ArchiveFormat::TarGz),
+            "zip" => Some(ArchiveFormat::Zip),
+            _ => None,
+        }
+    }
+
+    // guess the format from the name of the output file
+    pub fn from_file_name(file: &str) -> Option<ArchiveFormat> {
+        let name = file.to_lowercase();
+        [".tar.gz", ".tgz", ".tar", ".zip"]
+            .iter()
+            .find(|ext| name.ends_with(*ext))
+            .and_then(|ext| ArchiveFormat::parse(&ext[1..]))
+    }
+}
+
+// a file, link or directory of the archive, in tree order
+struct ArchiveEntry {
+    path: String,
+    object_type: ObjectType,
+    mode: u32,
+    hash: String,
+}
+
+/// Write the tree of a commit into an archive without touching the workspace.
+/// Every entry gets the commit date as its time, so the same version always
+/// gives the same archive.
+pub fn write_archive(
+    svc_path: PathBuf,
+    tree_hash: &str,
+    date: &str,
+    format: ArchiveFormat,
+    output: &Path,
+    prefix: &str,
+) -> Result<usize, Error> {
+    let mut entries = Vec::new();
+    collect_entries(&svc_path, tree_hash, prefix, &mut entries)?;
+    // entries are dated with the wall time of the commit, like its log shows
+    let (date, _) = parse_date(date).ok_or_else(|| Error::other(format!("bad commit date '{}'", date)))?;
+    let file = BufWriter::new(File::create(output)?);
+    match format {
+        ArchiveFormat::Tar => write_tar(&svc_path, &entries, date, file)?.flush()?,
+        ArchiveFormat::TarGz => {
+            // the gzip header has no time, keeping the output reproducible
+            let encoder = GzEncoder::new(file, Compression::default());
+            write_tar(&svc_path, &entries, date, encoder)?.finish()?.flush()?
+        }
+        ArchiveFormat::Zip => write_zip(&svc_path, &entries, date, file)?,
+    }
+    Ok(entries.iter().filter(|entry| entry.object_type != ObjectType::ObjectTree).count())
+}
+
+fn collect_entries(svc_path: &Path, tree_hash: &str, prefix: &str, entries: &mut Vec<ArchiveEntry>) -> Result<(), Error> {
+    if prefix.ends_with('/') {
+        entries.push(ArchiveEntry {
+            path: prefix.to_string(),
+            object_type: ObjectType::ObjectTree,
+            mode: MODE_DIR,
+            hash: tree_hash.to_string(),
+        });
+    }
+    let mut records = TreeEntry::read_tree(object_path(svc_path, tree_hash))?;
+    records.sort_by(|a, b| a.name.cmp(&b.name));
+    for record in records {
+        let path = format!("{}{}", prefix, record.name);
+        match record.object_type {
+            ObjectType::ObjectTree => collect_entries(svc_path, &record.hash, &format!("{}/", path), entries)?,
+            _ => entries.push(ArchiveEntry {
+                path,
+                object_type: record.object_type,
+                mode: record.mode,
+                hash: record.hash,
+            }),
+        }
+    }
+    Ok(())
+}
+
+fn write_tar<W: Write>(svc_path: &Path, entries: &[ArchiveEntry], date: NaiveDateTime, out: W) -> Result<W, Error> {
+    let mut builder = tar::Builder::new(out);
+    for entry in entries {
+        let mut header = tar::Header::new_gnu();
+        header.set_mode(entry.mode);
+        header.set_mtime(date.and_utc().timestamp().max(0) as u64);
+        header.set_uid(0);
+        header.set_gid(0);
+        match entry.object_type {
+            ObjectType::ObjectTree => {
+                header.set_entry_type(tar::EntryType::Directory);
+                header.set_size(0);
+                builder.append_data(&mut header, &entry.path, std::io::empty())?;
+            }
+            ObjectType::ObjectLink => {
+                let target = String::from_utf8_lossy(&TreeEntry::read_blob(object_path(svc_path, &entry.hash))?).to_string();
+                header.set_entry_type(tar::EntryType::Symlink);
+                header.set_size(0);
+                builder.append_link(&mut header, &entry.path, target)?;
+            }
+            ObjectType::ObjectBlob => {
+                let data = TreeEntry::read_blob(object_path(svc_path, &entry.hash))?;
+                header.set_entry_type(tar::EntryType::Regular);
+                header.set_size(data.len() as u64);
+                builder.append_data(&mut header, &entry.path, data.as_slice())?;
+            }
+        }
+    }
+    builder.into_inner()
+}
+
+fn write_zip<W: Write + std::io::Seek>(svc_path: &Path, entries: &[ArchiveEntry], date: NaiveDateTime, out: W) -> Result<(), Error> {
+    use chrono::{Datelike, Timelike};
+    let mut zip = ZipWriter::new(out);
+    // zip times can't go before 1980
+    let time = DateTime::from_date_and_time(
+        date.year().max(1980) as u16,
+        date.month() as u8,
+        date.day() as u8,
+        date.hour() as u8,
+        date.minute() as u8,
+        0,
+    )
+    .unwrap_or_default();
+    for entry in entries {
+        let options = SimpleFileOptions::default()
+            .compression_method(CompressionMethod::Deflated)
+            .last_modified_time(time)
+            .unix_permissions(entry.mode);
+        match entry.object_type {
+            ObjectType::ObjectTree => zip.add_directory(entry.path.as_str(), options)?,
+            ObjectType::ObjectLink => {
+                let target = String::from_utf8_lossy(&TreeEntry::read_blob(object_path(svc_path, &entry.hash))?).to_string();
+                zip.add_symlink(entry.path.as_str(), target, options)?;
+            }
+            ObjectType::ObjectBlob => {
+                zip.start_file(entry.path.as_str(), options)?;
+                zip.write_all(&TreeEntry::read_blob(object_path(svc_path, &entry.hash))?)?;
+            }
+        }
+    }
+    zip.finish()?.flush()
+}
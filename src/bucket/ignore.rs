@@ -0,0 +1,223 @@
+use ignore::{
+    gitignore::{Gitignore, GitignoreBuilder},
+    Match,
+};
+use std::{
+    fs::{self, File},
+    io::{BufRead, BufReader},
+    path::{Path, PathBuf},
+};
+
+pub const IGNORE_FILE: &str = ".svcignore";
+
+/// Gitignore-style rules collected from every `.svcignore` in the workspace.
+pub struct Ignore {
+    svc_path: PathBuf,
+    matchers: Vec<IgnoreFile>,
+}
+
+struct IgnoreFile {
+    dir: PathBuf,
+    matcher: Gitignore,
+    // (pattern, line number) of every rule, used to explain a match
+    lines: Vec<(String, usize)>,
+}
+
+/// The rule deciding whether a path is ignored.
+pub struct IgnoreRule {
+    pub source: PathBuf,
+    pub line: usize,
+    pub pattern: String,
+    pub negated: bool,
+}
+
+impl Ignore {
+    pub fn new(root: PathBuf) -> Ignore {
+        let mut ignore = Ignore {
+            svc_path: root.join(".svc"),
+            matchers: Vec::new(),
+        };
+        ignore.load_dir(root);
+        ignore
+    }
+
+    // load rules top-down so that ignored directories are never scanned
+    fn load_dir(&mut self, dir: PathBuf) {
+        let ignore_path = dir.join(IGNORE_FILE);
+        if let Ok(file) = File::open(&ignore_path) {
+            let mut builder = GitignoreBuilder::new(&dir);
+            let mut lines = Vec::new();
+            for (i, line) in BufReader::new(file).lines().enumerate() {
+                let line = line.unwrap();
+                match builder.add_line(Some(ignore_path.clone()), &line) {
+                    Ok(_) => lines.push((line.trim_end().to_string(), i + 1)),
+                    Err(err) => eprintln!("warning: {}:{}: {}", ignore_path.display(), i + 1, err),
+                }
+            }
+            match builder.build() {
+                Ok(matcher) => self.matchers.push(IgnoreFile {
+                    dir: dir.clone(),
+                    matcher,
+                    lines,
+                }),
+                Err(err) => eprintln!("warning: {}: {}", ignore_path.display(), err),
+            }
+        }
+        let entries = match fs::read_dir(&dir) {
+            Ok(entries) => entries,
+            Err(_) => return,
+        };
+        for entry in entries {
+            let entry = entry.unwrap();
+            if !entry.file_type().unwrap().is_dir() || self.is_ignored(&entry.path(), true) {
+                continue;
+            }
+            self.load_dir(entry.path());
+        }
+    }
+
+    /// Check a single path against the rules, assuming its parent directories
+    /// are not ignored. The `.svc` directory is always ignored.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        if path == self.svc_path {
+            return true;
+        }
+        match self.matched_rule(path, is_dir) {
+            Some(rule) => !rule.negated,
+            None => false,
+        }
+    }
+
+    /// Find the rule deciding a path, rules in deeper `.svcignore` files take
+    /// precedence over the ones closer to the root.
+    pub fn matched_rule(&self, path: &Path, is_dir: bool) -> Option<IgnoreRule> {
+        for file in self.matchers.iter().rev() {
+            if !path.starts_with(&file.dir) || path == file.dir {
+                continue;
+            }
+            let glob = match file.matcher.matched(path, is_dir) {
+                Match::None => continue,
+                Match::Ignore(glob) | Match::Whitelist(glob) => glob,
+            };
+            let line = file
+                .lines
+                .iter()
+                .rev()
+                .find(|(pattern, _)| pattern == glob.original())
+                .map(|(_, line)| *line)
+                .unwrap_or(0);
+            return Some(IgnoreRule {
+                source: glob.from().unwrap_or(&file.dir).to_path_buf(),
+                line,
+                pattern: glob.original().to_string(),
+                negated: glob.is_whitelist(),
+            });
+        }
+        None
+    }
+
+    /// Like `matched_rule`, but a path inside an ignored directory is decided by
+    /// the rule excluding that directory.
+    pub fn explain(&self, root: &Path, path: &Path, is_dir: bool) -> Option<IgnoreRule> {
+        let relative = path.strip_prefix(root).ok()?;
+        let mut dir = root.to_path_buf();
+        let components: Vec<_> = relative.components().collect();
+        for (i, component) in components.iter().enumerate() {
+            dir.push(component);
+            if i + 1 == components.len() {
+                break;
+            }
+            if let Some(rule) = self.matched_rule(&dir, true) {
+                if !rule.negated {
+                    return Some(rule);
+                }
+            }
+        }
+        self.matched_rule(path, is_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    // a workspace holding the given files, removed again when dropped
+    struct Workspace(PathBuf);
+
+    impl Workspace {
+        fn new(name: &str, files: &[(&str, &str)]) -> Workspace {
+            let root = env::temp_dir().join(format!("svc-ignore-{}-{}", name, process::id()));
+            let _ = fs::remove_dir_all(&root);
+            for (path, data) in files {
+                let path = root.join(path);
+                fs::create_dir_all(path.parent().unwrap()).unwrap();
+                fs::write(path, data).unwrap();
+            }
+            Workspace(root)
+        }
+
+        fn ignored(&self, path: &str, is_dir: bool) -> bool {
+            Ignore::new(self.0.clone()).is_ignored(&self.0.join(path), is_dir)
+        }
+    }
+
+    impl Drop for Workspace {
+        fn drop(&mut self) {
+            let _ = fs::remove_dir_all(&self.0);
+        }
+    }
+
+    #[test]
+    fn negated_rules_include_again() {
+        let workspace = Workspace::new("negate", &[(".svcignore", "*.log\n!keep.log\n")]);
+        assert!(workspace.ignored("debug.log", false));
+        assert!(!workspace.ignored("keep.log", false));
+        assert!(workspace.ignored(".svc", true));
+    }
+
+    #[test]
+    fn anchored_and_directory_rules() {
+        let workspace = Workspace::new("anchored", &[(".svcignore", "/build\nout/\n")]);
+        assert!(workspace.ignored("build", true));
+        assert!(!workspace.ignored("src/build", true));
+        assert!(workspace.ignored("out", true));
+        assert!(workspace.ignored("src/out", true));
+        // "out/" only matches directories
+        assert!(!workspace.ignored("out", false));
+    }
+
+    #[test]
+    fn double_star_matches_any_depth() {
+        let workspace = Workspace::new("double-star", &[(".svcignore", "**/cache\nlogs/**/*.tmp\n")]);
+        assert!(workspace.ignored("cache", true));
+        assert!(workspace.ignored("a/b/cache", true));
+        assert!(workspace.ignored("logs/x.tmp", false));
+        assert!(workspace.ignored("logs/a/b/x.tmp", false));
+        assert!(!workspace.ignored("other/x.tmp", false));
+    }
+
+    #[test]
+    fn deeper_files_take_precedence() {
+        let workspace = Workspace::new(
+            "nested",
+            &[(".svcignore", "*.txt\n"), ("docs/.svcignore", "!notes.txt\n"), ("docs/notes.txt", "")],
+        );
+        assert!(workspace.ignored("notes.txt", false));
+        assert!(!workspace.ignored("docs/notes.txt", false));
+        assert!(workspace.ignored("docs/other.txt", false));
+    }
+
+    #[test]
+    fn explain_names_the_deciding_rule() {
+        let workspace = Workspace::new("explain", &[(".svcignore", "# build output\nbuild/\n!*.keep\n")]);
+        let ignore = Ignore::new(workspace.0.clone());
+        // a path inside an ignored directory is decided by that directory's rule
+        let rule = ignore.explain(&workspace.0, &workspace.0.join("build/a.keep"), false).unwrap();
+        assert_eq!((rule.pattern.as_str(), rule.line, rule.negated), ("build/", 2, false));
+        assert_eq!(rule.source, workspace.0.join(IGNORE_FILE));
+        let rule = ignore.explain(&workspace.0, &workspace.0.join("a.keep"), false).unwrap();
+        assert_eq!((rule.pattern.as_str(), rule.line, rule.negated), ("!*.keep", 3, true));
+        assert!(ignore.explain(&workspace.0, &workspace.0.join("a.txt"), false).is_none());
+    }
+}
@@ -1,86 +1,238 @@
 use crate::util::{get_str_hash, get_file_hash};
-use crate::bucket::tree::{Tree, TreeEntry, ObjectType};
-use chrono::Local;
+use crate::bucket::tree::{find_entry_in_tree, flatten_tree, get_file_mode, object_path, read_link_target, Tree, TreeEntry, ObjectType, MODE_LINK};
+use crate::bucket::ignore::Ignore;
+use chrono::{DateTime, FixedOffset, Local, NaiveDateTime};
 use std::path::Component;
 use std::{
-    collections::HashMap,
+    collections::{BTreeMap, HashMap, VecDeque},
     fs::{self, File, OpenOptions},
-    io::{BufRead, BufReader, Error, Read, Write},
-    path::PathBuf,
+    io::{Error, Read, Write},
+    path::{Path, PathBuf},
     process
 };
 
+pub const NO_PARENT: &str = "0000000000000000000000000000000000000000";
+
+// commits replaced by "svc commit --amend", as log lines
+pub const AMENDED: &str = "amended";
+
+// lines with an author or an escaped message start their message part with
+// this word, older lines are always read as they are
+const LOG_VERSION: &str = "v=2";
+
+// an optional author is written between the version and the message
+const AUTHOR_PREFIX: &str = "author=";
+
+#[derive(Clone)]
 pub struct Commit {
     pub hash: String,
     pub parent_hash: String,
     pub tree_hash: String,
     pub date: String,
+    pub author: String,
     pub message: String,
 }
 
 impl Commit {
-    pub fn new(message: String, svc_path: PathBuf) -> Commit {
-        let date = Local::now().format("%Y-%m-%d %H:%M").to_string();
-        let str = date.clone() + &message[..] + "commit";
-        let hash = get_str_hash(&str);
+    pub fn new(message: String, svc_path: PathBuf, merge_hash: Option<&str>, tree_hash: String) -> Commit {
         let mut parent_hash = Commit::get_head_hash(svc_path.clone());
         // first commit has no parent
-        if parent_hash == "" {
-            parent_hash = String::from("0000000000000000000000000000000000000000");
+        if parent_hash.is_empty() {
+            parent_hash = String::from(NO_PARENT);
         }
+        // a merge commit lists both parents, separated by a comma
+        if let Some(merge_hash) = merge_hash {
+            parent_hash = format!("{},{}", parent_hash, merge_hash);
+        }
+        Commit::from_tree(message, parent_hash, tree_hash)
+    }
+
+    // save the whole workspace as a tree and return its hash
+    pub fn save_workspace(svc_path: PathBuf) -> String {
         let exclude = Commit::read_ignore(svc_path.clone());
-        let tree_hash = Tree::new(
+        Tree::new(
             svc_path.clone().parent().unwrap().to_path_buf(),
             svc_path,
             &exclude,
         )
-        .hash;
+        .hash
+    }
+
+    /// Save the workspace state of the given paths, relative to the root, over
+    /// the HEAD tree and return the hash of the new tree. Every path has to be
+    /// in the workspace or in HEAD, nothing else of the workspace is read.
+    pub fn save_paths(svc_path: PathBuf, head_tree: Option<&str>, paths: &[String]) -> Result<String, Error> {
+        let root_path = svc_path.parent().unwrap().to_path_buf();
+        let exclude = Commit::read_ignore(svc_path.clone());
+        let mut entries = match head_tree {
+            Some(head_tree) => flatten_tree(svc_path.clone(), head_tree)?,
+            None => BTreeMap::new(),
+        };
+        let mut saved = BTreeMap::new();
+        for path in paths {
+            let file_type = match workspace_file_type(&root_path, path, &exclude) {
+                Some(file_type) => file_type,
+                // removed from the workspace
+                None if entries.keys().any(|entry_path| is_under(entry_path, path)) => continue,
+                None => return Err(Error::other(format!("'{}' did not match any file", path))),
+            };
+            let entry = TreeEntry::save(root_path.join(path), file_type, svc_path.clone(), &exclude)?;
+            if entry.object_type != ObjectType::ObjectTree {
+                saved.insert(path.clone(), entry);
+                continue;
+            }
+            let files = flatten_tree(svc_path.clone(), &entry.hash)?;
+            if files.is_empty() && !path.is_empty() {
+                saved.insert(path.clone(), entry);
+            }
+            for (file_path, file_entry) in files {
+                match path.as_str() {
+                    "" => saved.insert(file_path, file_entry),
+                    _ => saved.insert(format!("{}/{}", path, file_path), file_entry),
+                };
+            }
+        }
+        entries.retain(|entry_path, _| !paths.iter().any(|path| is_under(entry_path, path)));
+        entries.extend(saved);
+        Ok(Tree::from_entries(svc_path, &entries)?.hash)
+    }
+
+    pub fn from_tree(message: String, parent_hash: String, tree_hash: String) -> Commit {
+        Commit::with_author(message, parent_hash, tree_hash, current_date(), String::new())
+    }
+
+    // a commit made by someone else at another time, e.g. an imported one
+    pub fn with_author(message: String, parent_hash: String, tree_hash: String, date: String, author: String) -> Commit {
+        let str = date.clone() + &message[..] + &parent_hash + &tree_hash + &author + "commit";
+        let hash = get_str_hash(&str);
         Commit {
             hash,
             parent_hash,
             tree_hash,
             message,
             date,
+            author,
+        }
+    }
+
+    pub fn to_log_line(&self) -> String {
+        let mut line = format!("{} {} {} {} ", self.hash, self.parent_hash, self.tree_hash, self.date);
+        let message = escape_message(&self.message);
+        // a plain message is written like older versions did, unless it
+        // starts with the version itself
+        if self.author.is_empty() && message == self.message && strip_word(&self.message, LOG_VERSION).is_none() {
+            return line + &message + "\n";
+        }
+        line += &format!("{} ", LOG_VERSION);
+        // an empty author still marks a message that looks like one
+        if !self.author.is_empty() || message.starts_with(AUTHOR_PREFIX) {
+            line += &format!("{}{} ", AUTHOR_PREFIX, escape_author(&self.author));
+        }
+        line + &message + "\n"
+    }
+
+    pub fn parents(&self) -> Vec<String> {
+        self.parent_hash
+            .split(',')
+            .filter(|hash| *hash != NO_PARENT)
+            .map(|hash| hash.to_string())
+            .collect()
+    }
+
+    // find a commit of the log by "HEAD" or a prefix of its hash, or else one
+    // replaced by "svc commit --amend", so it can still be read
+    pub fn find_commit(svc_path: PathBuf, version: &str) -> Result<Commit, &'static str> {
+        Commit::find(svc_path, version, true)
+    }
+
+    // the same for commands that move HEAD, which has to stay in the log
+    pub fn find_logged_commit(svc_path: PathBuf, version: &str) -> Result<Commit, &'static str> {
+        Commit::find(svc_path, version, false)
+    }
+
+    fn find(svc_path: PathBuf, version: &str, with_amended: bool) -> Result<Commit, &'static str> {
+        let version = if version == "HEAD" {
+            Commit::get_head_hash(svc_path.clone())
+        } else {
+            version.to_string()
+        };
+        if version.is_empty() {
+            return Err("version not found.");
+        }
+        let matching = |commits: Vec<Commit>| -> Vec<Commit> {
+            commits.into_iter().filter(|commit| commit.hash.starts_with(&version)).collect()
+        };
+        let mut targets = matching(Commit::read_from_log(svc_path.clone()));
+        if targets.is_empty() {
+            let mut amended = matching(Commit::parse_log(&fs::read_to_string(svc_path.join(AMENDED)).unwrap_or_default()));
+            // a commit may be amended again after being pulled back
+            amended.sort_by(|a, b| a.hash.cmp(&b.hash));
+            amended.dedup_by(|a, b| a.hash == b.hash);
+            if !with_amended && !amended.is_empty() {
+                return Err("version was replaced by \"svc commit --amend\", get its files back with \"svc restore --source <version> .\".");
+            }
+            targets = amended;
+        }
+        match targets.len() {
+            0 => Err("version not found."),
+            1 => Ok(targets.remove(0)),
+            _ => Err("found more than one version matches."),
         }
     }
 
     pub fn write_to_log(commit: &Commit, svc_path: PathBuf) -> Result<(), Error> {
         let mut file = OpenOptions::new().append(true).open(svc_path.join("log"))?;
-        file.write_fmt(format_args!(
-            "{} {} {} {} {}\n",
-            commit.hash, commit.parent_hash, commit.tree_hash, commit.date, commit.message
-        ))?;
+        file.write_all(commit.to_log_line().as_bytes())?;
         Ok(())
     }
 
-    pub fn read_ignore(svc_path: PathBuf) -> HashMap<PathBuf, bool>{
-        // exclude files declared in '.svcignore'
-        let mut exclude: HashMap<PathBuf, bool> = HashMap::new();
-        if let Ok(file) = File::open(svc_path.parent().unwrap().join(".svcignore")) {
-            let reader = BufReader::new(file);
-            for line in reader.lines() {
-                let line = line.unwrap();
-                let path = svc_path.parent().unwrap().join(line.trim());
-                println!("exclude {:?}", path);
-                exclude.insert(path, true);
-            }
+    // replace the whole log, used when commits are added out of order
+    pub fn write_log(commits: &[Commit], svc_path: PathBuf) -> Result<(), Error> {
+        let mut log = String::new();
+        for commit in commits {
+            log += &commit.to_log_line();
         }
-        exclude
+        let tmp_path = svc_path.join("log.tmp");
+        fs::write(&tmp_path, log)?;
+        fs::rename(tmp_path, svc_path.join("log"))
+    }
+
+    pub fn read_ignore(svc_path: PathBuf) -> Ignore {
+        // exclude files matched by rules in '.svcignore' files
+        Ignore::new(svc_path.parent().unwrap().to_path_buf())
     }
 
     pub fn read_from_log(svc_path: PathBuf) -> Vec<Commit> {
+        let mut log = String::new();
+        File::open(svc_path.join("log")).unwrap().read_to_string(&mut log).unwrap();
+        Commit::parse_log(&log)
+    }
+
+    pub fn parse_log(log: &str) -> Vec<Commit> {
         let mut commits: Vec<Commit> = Vec::new();
-        let file = File::open(svc_path.join("log")).unwrap();
-        let reader = BufReader::new(file);
-        for line in reader.lines() {
-            let line = line.unwrap();
-            let line: Vec<&str> = line.split_whitespace().collect();
+        for line in log.lines() {
+            let line: Vec<&str> = line.trim_end().splitn(6, ' ').collect();
+            if line.len() < 5 {
+                continue;
+            }
+            let rest = line.get(5).copied().unwrap_or("");
+            let (author, message) = match strip_word(rest, LOG_VERSION) {
+                Some(rest) => {
+                    let (author, message) = match rest.strip_prefix(AUTHOR_PREFIX) {
+                        Some(rest) => rest.split_once(' ').unwrap_or((rest, "")),
+                        None => ("", rest),
+                    };
+                    (unescape(author, '%'), unescape(message, '\\'))
+                }
+                None => (String::new(), rest.to_string()),
+            };
             commits.push(Commit {
                 hash: line[0].to_string(),
                 parent_hash: line[1].to_string(),
                 tree_hash: line[2].to_string(),
                 date: line[3].to_string() + " " + line[4],
-                message: line[5..].join(" "),
+                author,
+                message,
             })
         }
         commits
@@ -95,32 +247,167 @@ impl Commit {
 
     pub fn reset_head(svc_path: PathBuf, head_hash: String) {
         let mut file = File::create(svc_path.join("head")).unwrap();
-        file.write(head_hash.as_bytes()).unwrap();
+        file.write_all(head_hash.as_bytes()).unwrap();
     }
 
     pub fn restore_tree(dir: PathBuf, svc_path: PathBuf, tree_hash: String) -> Result<(), Error> {
+        Commit::restore_tree_into(svc_path.parent().unwrap().to_path_buf(), dir, svc_path, tree_hash)
+    }
+
+    // restore a tree anywhere below `root`, only files of the workspace are
+    // checked for unsaved changes
+    pub fn restore_tree_into(root_path: PathBuf, dir: PathBuf, svc_path: PathBuf, tree_hash: String) -> Result<(), Error> {
+        check_inside_workspace(root_path.clone(), dir.clone())?;
+        let in_workspace = root_path == svc_path.parent().unwrap();
         let tree_dir = svc_path.join("objects").join(&tree_hash[0..2]);
         let tree_path = tree_dir.join(&tree_hash[2..]);
-        let tree_entries = TreeEntry::read_tree(tree_path);
+        let tree_entries = TreeEntry::read_tree(tree_path)?;
         for entry in tree_entries {
+            let entry_path = dir.join(entry.name.clone());
+            let exists = fs::symlink_metadata(&entry_path).is_ok();
             match entry.object_type {
-                ObjectType::ObjectBlob => {
+                ObjectType::ObjectBlob | ObjectType::ObjectLink => {
                     let blob_dir = svc_path.join("objects").join(&entry.hash[0..2]);
                     let blob_path = blob_dir.join(&entry.hash[2..]);
-                    if let Err("doesn't match")  = check_blob_state(dir.join(entry.name.clone()), svc_path.clone()) {
-                        eprintln!("error: \'{}\' was modified but not saved." , dir.join(entry.name).to_str().unwrap());
+                    if in_workspace && exists && check_blob_state(entry_path.clone(), svc_path.clone()) == Err("doesn't match") {
+                        eprintln!("error: \'{}\' was modified but not saved." , entry_path.to_str().unwrap());
                         eprintln!("error: forced version switching will result in data loss.");
                         process::exit(1);
                     }
-                    TreeEntry::restore_blob(dir.clone().join(entry.name), blob_path)?;
+                    if let ObjectType::ObjectLink = entry.object_type {
+                        TreeEntry::restore_link(entry_path, blob_path)?;
+                    } else {
+                        TreeEntry::restore_blob(entry_path, blob_path, entry.mode)?;
+                    }
                 }
                 ObjectType::ObjectTree => {
-                    match fs::create_dir(dir.join(entry.name.clone())) {
+                    // replace a link with a real directory instead of following it
+                    if exists && fs::symlink_metadata(&entry_path)?.file_type().is_symlink() {
+                        fs::remove_file(&entry_path)?;
+                    }
+                    match fs::create_dir(entry_path.clone()) {
                         Ok(_) => (),
                         Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => (),
                         Err(e) => return Err(e),
                     };
-                    Commit::restore_tree(dir.join(entry.name), svc_path.clone(), entry.hash)?;
+                    Commit::restore_tree_into(root_path.clone(), entry_path, svc_path.clone(), entry.hash)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // remove directories tracked by the old tree but absent from the new one,
+    // as long as nothing is left inside them
+    pub fn remove_stale_dirs(svc_path: PathBuf, old_tree_hash: String, new_tree_hash: String) -> Result<(), Error> {
+        let root_path = svc_path.parent().unwrap().to_path_buf();
+        Commit::remove_stale_dirs_in(root_path, svc_path, old_tree_hash, new_tree_hash)
+    }
+
+    // the same for two versions of the directory `dir`
+    pub fn remove_stale_dirs_in(dir: PathBuf, svc_path: PathBuf, old_tree_hash: String, new_tree_hash: String) -> Result<(), Error> {
+        let mut old_dirs = Vec::new();
+        let mut new_dirs = Vec::new();
+        get_dirs_in_tree(svc_path.clone(), old_tree_hash, dir.clone(), &mut old_dirs)?;
+        get_dirs_in_tree(svc_path, new_tree_hash, dir, &mut new_dirs)?;
+        for (dir, _) in old_dirs.iter().rev() {
+            if new_dirs.iter().any(|(new_dir, _)| new_dir == dir) {
+                continue;
+            }
+            if let Ok(meta) = fs::symlink_metadata(dir) {
+                if meta.is_dir() {
+                    let _ = fs::remove_dir(dir);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // remove files and links tracked by the old tree but absent from the new
+    // one, files modified since are kept
+    pub fn remove_stale_files(svc_path: PathBuf, old_tree_hash: &str, new_tree_hash: &str) -> Result<(), Error> {
+        let root_path = svc_path.parent().unwrap().to_path_buf();
+        let new_entries = flatten_tree(svc_path.clone(), new_tree_hash)?;
+        for (path, entry) in flatten_tree(svc_path.clone(), old_tree_hash)? {
+            if entry.object_type == ObjectType::ObjectTree || new_entries.contains_key(&path) {
+                continue;
+            }
+            let file_path = root_path.join(&path);
+            let file_hash = match fs::symlink_metadata(&file_path) {
+                Ok(meta) if meta.file_type().is_symlink() => get_str_hash(&read_link_target(file_path.clone())),
+                Ok(meta) if meta.is_file() => get_file_hash(file_path.clone()),
+                _ => continue,
+            };
+            if file_hash == entry.hash {
+                let _ = fs::remove_file(file_path);
+            } else {
+                eprintln!("warning: '{}' was modified, keep it.", path);
+            }
+        }
+        Ok(())
+    }
+
+    // write an entry of a version over the workspace, discarding any change to
+    // it. Given the tree the workspace was at, a directory also loses the files
+    // that tree has and the version doesn't.
+    pub fn restore_path(svc_path: PathBuf, relative_path: &str, entry: TreeEntry, old_tree: Option<&str>) -> Result<(), Error> {
+        let root_path = svc_path.parent().unwrap().to_path_buf();
+        let entry_path = root_path.join(relative_path);
+        // links in the workspace must not lead the restore outside of it
+        let mut parent = match relative_path.rsplit_once('/') {
+            Some((dir, _)) => root_path.join(dir),
+            None => root_path.clone(),
+        };
+        while fs::symlink_metadata(&parent).is_err() {
+            parent.pop();
+        }
+        check_inside_workspace(root_path.clone(), parent)?;
+        fs::create_dir_all(entry_path.parent().unwrap())?;
+
+        let existing = fs::symlink_metadata(&entry_path).ok();
+        let blob_path = object_path(&svc_path, &entry.hash);
+        match entry.object_type {
+            ObjectType::ObjectBlob | ObjectType::ObjectLink => {
+                if existing.is_some_and(|meta| meta.is_dir()) {
+                    return Err(Error::other(format!("'{}' is a directory in the workspace", relative_path)));
+                }
+                match entry.object_type {
+                    ObjectType::ObjectLink => TreeEntry::restore_link(entry_path, blob_path)?,
+                    _ => TreeEntry::restore_blob(entry_path, blob_path, entry.mode)?,
+                }
+            }
+            ObjectType::ObjectTree => {
+                if existing.is_some_and(|meta| !meta.is_dir()) {
+                    fs::remove_file(&entry_path)?;
+                }
+                fs::create_dir_all(&entry_path)?;
+                for child in TreeEntry::read_tree(object_path(&svc_path, &entry.hash))? {
+                    let child_path = match relative_path {
+                        "" => child.name.clone(),
+                        _ => format!("{}/{}", relative_path, child.name),
+                    };
+                    Commit::restore_path(svc_path.clone(), &child_path, child, None)?;
+                }
+                let old_tree = match old_tree {
+                    Some(old_tree) if relative_path.is_empty() => Some(old_tree.to_string()),
+                    Some(old_tree) => find_entry_in_tree(&svc_path, old_tree, relative_path)?
+                        .filter(|old_entry| old_entry.object_type == ObjectType::ObjectTree)
+                        .map(|old_entry| old_entry.hash),
+                    None => None,
+                };
+                if let Some(old_tree) = old_tree {
+                    let new_entries = flatten_tree(svc_path.clone(), &entry.hash)?;
+                    for (path, old_entry) in flatten_tree(svc_path.clone(), &old_tree)? {
+                        let file_path = entry_path.join(&path);
+                        if old_entry.object_type == ObjectType::ObjectTree
+                            || new_entries.contains_key(&path)
+                            || check_inside_workspace(root_path.clone(), file_path.parent().unwrap().to_path_buf()).is_err()
+                        {
+                            continue;
+                        }
+                        let _ = fs::remove_file(file_path);
+                    }
+                    Commit::remove_stale_dirs_in(entry_path, svc_path, old_tree, entry.hash)?;
                 }
             }
         }
@@ -148,15 +435,9 @@ impl Commit {
         for commit in commits {
             println!("commit: {:?}", commit.hash);
             if remove_flag {
-                file_log_bak.write_fmt(format_args!(
-                    "{} {} {} {} {}\n",
-                    commit.hash, commit.parent_hash, commit.tree_hash, commit.date, commit.message
-                )).unwrap();
+                file_log_bak.write_all(commit.to_log_line().as_bytes()).unwrap();
             } else {
-                file_log.write_fmt(format_args!(
-                    "{} {} {} {} {}\n",
-                    commit.hash, commit.parent_hash, commit.tree_hash, commit.date, commit.message
-                )).unwrap();
+                file_log.write_all(commit.to_log_line().as_bytes()).unwrap();
             }
             if commit.hash == head_commit_hash {
                 remove_flag = true;
@@ -165,12 +446,58 @@ impl Commit {
     }
 }
 
+// a log line holds one commit, so line breaks in messages are escaped
+fn escape_message(message: &str) -> String {
+    message.replace('\\', "\\\\").replace('\n', "\\n").replace('\r', "\\r")
+}
+
+// the author is a single field of the log line
+fn escape_author(author: &str) -> String {
+    author.replace('%', "%25").replace(' ', "%20").replace('\n', "%0A").replace('\r', "%0D")
+}
+
+// the rest of `data` after its first word, if that is `word`
+fn strip_word<'a>(data: &'a str, word: &str) -> Option<&'a str> {
+    match data.strip_prefix(word)? {
+        "" => Some(""),
+        rest => rest.strip_prefix(' '),
+    }
+}
+
+fn unescape(data: &str, escape: char) -> String {
+    let mut result = String::new();
+    let mut chars = data.chars();
+    while let Some(c) = chars.next() {
+        if c != escape {
+            result.push(c);
+            continue;
+        }
+        let rest = chars.as_str();
+        let (replacement, len) = match escape {
+            '\\' if rest.starts_with('n') => ("\n", 1),
+            '\\' if rest.starts_with('r') => ("\r", 1),
+            '\\' if rest.starts_with('\\') => ("\\", 1),
+            '%' if rest.starts_with("25") => ("%", 2),
+            '%' if rest.starts_with("20") => (" ", 2),
+            '%' if rest.starts_with("0A") => ("\n", 2),
+            '%' if rest.starts_with("0D") => ("\r", 2),
+            // anything else was never escaped
+            _ => {
+                result.push(c);
+                continue;
+            }
+        };
+        result.push_str(replacement);
+        chars = rest[len..].chars();
+    }
+    result
+}
+
 pub fn check_blob_state(file_path: PathBuf, svc_path: PathBuf) -> Result<(), &'static str>{
     let mut relative_path = Vec::new();
     // let file_path = dir.join(filename.clone());
-    let mut file_components = file_path.components();
     let mut svc_components = svc_path.parent().unwrap().components();
-    while let Some(c1) = file_components.next() {
+    for c1 in file_path.components() {
         if let Some(c2) = svc_components.next() {
             if c1 == c2 {
                 continue;
@@ -181,18 +508,81 @@ pub fn check_blob_state(file_path: PathBuf, svc_path: PathBuf) -> Result<(), &'s
     // println!("{:?}", relative_path);
     let head_hash = Commit::get_head_hash(svc_path.clone());
     if let Ok(tree_hash) = get_tree_of_commit(svc_path.clone(), head_hash) {
-        if let Ok(blob_hash) = get_blob_hash_from_entry(svc_path.clone(), tree_hash, relative_path) {
-            let file_hash = get_file_hash(file_path);
-            if blob_hash == file_hash {
-                return Ok(());
+        if let Ok((blob_hash, blob_mode)) = get_blob_hash_from_entry(svc_path.clone(), tree_hash, relative_path) {
+            let (file_hash, file_mode) = match fs::symlink_metadata(&file_path) {
+                Ok(meta) if meta.file_type().is_symlink() => (get_str_hash(&read_link_target(file_path)), MODE_LINK),
+                _ => (get_file_hash(file_path.clone()), get_file_mode(file_path)),
+            };
+            if blob_hash != file_hash {
+                return Err("doesn't match");
             }
-            return Err("doesn't match");
+            if blob_mode != file_mode {
+                return Err("mode changed");
+            }
+            return Ok(());
         }
     }
     Err("not found")
 }
 
-fn get_tree_of_commit(svc_path: PathBuf, commit_hash: String) -> Result<String, ()> {
+// whether a path is the given one or inside it, "" being the root
+fn is_under(entry_path: &str, path: &str) -> bool {
+    path.is_empty() || entry_path == path || entry_path.strip_prefix(path).is_some_and(|rest| rest.starts_with('/'))
+}
+
+// the type of a path of the workspace, unless it is ignored or reached
+// through something other than a directory
+fn workspace_file_type(root_path: &Path, path: &str, exclude: &Ignore) -> Option<fs::FileType> {
+    let mut current = root_path.to_path_buf();
+    let mut file_type = fs::metadata(&current).ok()?.file_type();
+    for part in path.split('/').filter(|part| !part.is_empty()) {
+        if !file_type.is_dir() {
+            return None;
+        }
+        current.push(part);
+        file_type = fs::symlink_metadata(&current).ok()?.file_type();
+        if exclude.is_ignored(&current, file_type.is_dir()) {
+            return None;
+        }
+    }
+    Some(file_type)
+}
+
+// commit dates are local times to the minute
+pub fn current_date() -> String {
+    Local::now().format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// The time of a commit date, and the timezone offset imported commits carry
+/// after it, as in "2024-01-02 03:04+0800". Dates without one are local.
+pub fn parse_date(date: &str) -> Option<(NaiveDateTime, Option<FixedOffset>)> {
+    match date.split_at_checked("YYYY-MM-DD HH:MM".len()) {
+        Some((time, offset)) if !offset.is_empty() => {
+            let date = DateTime::parse_from_str(&format!("{}:00{}", time, offset), "%Y-%m-%d %H:%M:%S%z").ok()?;
+            Some((date.naive_local(), Some(*date.offset())))
+        }
+        _ => Some((NaiveDateTime::parse_from_str(&format!("{}:00", date), "%Y-%m-%d %H:%M:%S").ok()?, None)),
+    }
+}
+
+/// Every commit reachable from `hash` through parent links, nearest first.
+pub fn get_ancestors(commits: &[Commit], hash: &str) -> Vec<String> {
+    let commits: HashMap<&str, &Commit> = commits.iter().map(|commit| (commit.hash.as_str(), commit)).collect();
+    let mut found = Vec::new();
+    let mut queue = VecDeque::from([hash.to_string()]);
+    while let Some(hash) = queue.pop_front() {
+        if found.contains(&hash) {
+            continue;
+        }
+        if let Some(commit) = commits.get(hash.as_str()) {
+            queue.extend(commit.parents());
+        }
+        found.push(hash);
+    }
+    found
+}
+
+pub fn get_tree_of_commit(svc_path: PathBuf, commit_hash: String) -> Result<String, ()> {
    let commits = Commit::read_from_log(svc_path);
    for commit in commits {
     if commit_hash == commit.hash {
@@ -202,15 +592,22 @@ fn get_tree_of_commit(svc_path: PathBuf, commit_hash: String) -> Result<String,
    Err(())
 }
 
-fn get_blob_hash_from_entry(svc_path: PathBuf, tree_hash: String, relative_path: Vec<Component>) -> Result<String, ()>{
+pub fn get_blob_hash_from_entry(svc_path: PathBuf, tree_hash: String, relative_path: Vec<Component>) -> Result<(String, u32), ()>{
     let tree_path = svc_path.join("objects").join(&tree_hash[0..2]).join(&tree_hash[2..]);
-    let tree_entries = TreeEntry::read_tree(tree_path);
-    
+    let tree_entries = TreeEntry::read_tree(tree_path).map_err(|_| ())?;
+    if relative_path.is_empty() {
+        return Err(());
+    }
+
     for entry in tree_entries {
         if entry.name == relative_path[0].as_os_str().to_str().unwrap() {
             match entry.object_type {
-                ObjectType::ObjectBlob => {
-                    return Ok(entry.hash);
+                // a file can't have anything below it
+                ObjectType::ObjectBlob | ObjectType::ObjectLink if relative_path.len() > 1 => {
+                    return Err(());
+                }
+                ObjectType::ObjectBlob | ObjectType::ObjectLink => {
+                    return Ok((entry.hash, entry.mode));
                 }
                 ObjectType::ObjectTree => {
                     return get_blob_hash_from_entry(svc_path, entry.hash, relative_path[1..].to_vec());
@@ -221,18 +618,169 @@ fn get_blob_hash_from_entry(svc_path: PathBuf, tree_hash: String, relative_path:
     Err(())
 }
 
-pub fn get_file_paths_in_dir(dir: PathBuf, files: &mut Vec<PathBuf>, exclude: HashMap<PathBuf, bool>) -> &Vec<PathBuf> {
+// collect every directory of a tree (parents before children) and whether it is empty
+pub fn get_dirs_in_tree(svc_path: PathBuf, tree_hash: String, dir: PathBuf, dirs: &mut Vec<(PathBuf, bool)>) -> Result<(), Error> {
+    let tree_path = svc_path.join("objects").join(&tree_hash[0..2]).join(&tree_hash[2..]);
+    for entry in TreeEntry::read_tree(tree_path)? {
+        if let ObjectType::ObjectTree = entry.object_type {
+            let entry_path = dir.join(&entry.name);
+            let index = dirs.len();
+            dirs.push((entry_path.clone(), false));
+            let sub_tree_path = svc_path.join("objects").join(&entry.hash[0..2]).join(&entry.hash[2..]);
+            dirs[index].1 = TreeEntry::read_tree(sub_tree_path)?.is_empty();
+            get_dirs_in_tree(svc_path.clone(), entry.hash, entry_path, dirs)?;
+        }
+    }
+    Ok(())
+}
+
+// refuse to restore into a directory reached through a link pointing outside the workspace
+fn check_inside_workspace(root_path: PathBuf, dir: PathBuf) -> Result<(), Error> {
+    let real_root = fs::canonicalize(root_path)?;
+    let real_dir = fs::canonicalize(&dir)?;
+    if !real_dir.starts_with(real_root) {
+        return Err(Error::other(format!(
+            "'{}' resolves outside the workspace",
+            dir.to_str().unwrap()
+        )));
+    }
+    Ok(())
+}
+
+// empty directories are the ones without any entry that would be saved
+pub fn get_empty_dirs_in_dir(dir: PathBuf, dirs: &mut Vec<PathBuf>, exclude: &Ignore) -> bool {
+    let mut is_empty = true;
+    for entry in fs::read_dir(&dir).unwrap() {
+        let entry = entry.unwrap();
+        let file_type = entry.file_type().unwrap();
+        if exclude.is_ignored(&entry.path(), file_type.is_dir()) {
+            continue;
+        }
+        is_empty = false;
+        if file_type.is_dir() && get_empty_dirs_in_dir(entry.path(), dirs, exclude) {
+            dirs.push(entry.path());
+        }
+    }
+    is_empty
+}
+
+pub fn get_file_paths_in_dir<'a>(dir: PathBuf, files: &'a mut Vec<PathBuf>, exclude: &Ignore) -> &'a Vec<PathBuf> {
     for entry in fs::read_dir(dir).unwrap() {
         let entry = entry.unwrap();
-        let entry_meta = entry.metadata().unwrap();
-        if exclude.contains_key(&entry.path()) || entry.file_name().to_str().unwrap().starts_with(".") {
+        let file_type = entry.file_type().unwrap();
+        if exclude.is_ignored(&entry.path(), file_type.is_dir()) {
             continue;
         }
-        if entry_meta.is_dir() {
-            return get_file_paths_in_dir(entry.path(), files, exclude);
+        if file_type.is_dir() {
+            get_file_paths_in_dir(entry.path(), files, exclude);
         } else {
             files.push(entry.path());
         }
     }
     files
-}
\ No newline at end of file
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{env, process};
+
+    #[cfg(unix)]
+    #[test]
+    fn refuse_to_restore_through_a_link_leaving_the_workspace() {
+        let tmp = env::temp_dir().join(format!("svc-restore-link-{}", process::id()));
+        let (root, outside) = (tmp.join("root"), tmp.join("outside"));
+        fs::create_dir_all(root.join(".svc")).unwrap();
+        fs::create_dir_all(&outside).unwrap();
+        std::os::unix::fs::symlink(&outside, root.join("dir")).unwrap();
+        let result = Commit::restore_tree(root.join("dir"), root.join(".svc"), "0".repeat(40));
+        let _ = fs::remove_dir_all(&tmp);
+        assert!(result.unwrap_err().to_string().contains("outside the workspace"));
+    }
+
+    fn commit(message: &str, author: &str) -> Commit {
+        let hash = "1".repeat(40);
+        Commit::with_author(message.to_string(), NO_PARENT.to_string(), hash, "2024-01-02 03:04".to_string(), author.to_string())
+    }
+
+    fn round_trip(commit: &Commit) -> Commit {
+        let mut commits = Commit::parse_log(&commit.to_log_line());
+        assert_eq!(commits.len(), 1);
+        commits.remove(0)
+    }
+
+    #[test]
+    fn escape_message_keeps_one_line() {
+        assert_eq!(escape_message("a\nb\r\nc"), "a\\nb\\r\\nc");
+        assert_eq!(escape_message("C:\\new"), "C:\\\\new");
+        assert_eq!(unescape(&escape_message("x\\ny\n\\\\"), '\\'), "x\\ny\n\\\\");
+        assert_eq!(unescape(&escape_author("a b%20\n"), '%'), "a b%20\n");
+        // unknown escapes are left alone
+        assert_eq!(unescape("a\\tb%zz", '\\'), "a\\tb%zz");
+    }
+
+    #[test]
+    fn parse_log_round_trip() {
+        for (message, author) in [
+            ("plain message", ""),
+            ("", ""),
+            ("two\nlines", ""),
+            ("fix C:\\new path", ""),
+            ("  spaced  words", ""),
+            ("author=not an author", ""),
+            ("v=2 looks like a version", ""),
+            ("v=2", ""),
+            ("imported", "Jane Doe <jane@example.com>"),
+            ("author=x", "a%b"),
+        ] {
+            let parsed = round_trip(&commit(message, author));
+            assert_eq!(parsed.message, message);
+            assert_eq!(parsed.author, author);
+            assert_eq!(parsed.date, "2024-01-02 03:04");
+            assert_eq!(parsed.parent_hash, NO_PARENT);
+        }
+    }
+
+    #[test]
+    fn plain_lines_keep_the_old_format() {
+        let line = commit("plain message", "").to_log_line();
+        assert!(line.ends_with(" 2024-01-02 03:04 plain message\n"));
+        let line = commit("a\nb", "").to_log_line();
+        assert!(line.ends_with(" 2024-01-02 03:04 v=2 a\\nb\n"));
+    }
+
+    #[test]
+    fn parse_log_reads_old_lines_as_they_are() {
+        let hash = "1".repeat(40);
+        let log = format!(
+            "{h} {p} {h} 2020-01-01 00:00 fix C:\\new path\n{h} {p} {h} 2020-01-01 00:01 author=someone wrote this\n",
+            h = hash,
+            p = NO_PARENT
+        );
+        let commits = Commit::parse_log(&log);
+        assert_eq!(commits.len(), 2);
+        assert_eq!(commits[0].message, "fix C:\\new path");
+        assert_eq!(commits[1].message, "author=someone wrote this");
+        assert_eq!(commits[1].author, "");
+    }
+
+    #[test]
+    fn parse_dates_with_offsets() {
+        let (time, offset) = parse_date("2024-01-02 03:04").unwrap();
+        assert_eq!(time.to_string(), "2024-01-02 03:04:00");
+        assert!(offset.is_none());
+        let (time, offset) = parse_date("2024-01-02 03:04+0800").unwrap();
+        assert_eq!(time.to_string(), "2024-01-02 03:04:00");
+        assert_eq!(offset.unwrap().local_minus_utc(), 8 * 3600);
+        let (_, offset) = parse_date("2024-01-02 03:04-0130").unwrap();
+        assert_eq!(offset.unwrap().local_minus_utc(), -5400);
+        assert!(parse_date("2024-01-02").is_none());
+        assert!(parse_date("2024-01-02 03:04 junk").is_none());
+        assert!(parse_date("2024-13-02 03:04").is_none());
+    }
+
+    #[test]
+    fn parse_log_skips_broken_lines() {
+        let commits = Commit::parse_log("\nnot a commit\n");
+        assert!(commits.is_empty());
+    }
+}
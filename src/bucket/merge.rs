@@ -0,0 +1,488 @@
+use crate::bucket::log::{get_ancestors, get_tree_of_commit, Commit};
+use crate::bucket::repo::RepoMeta;
+use crate::bucket::tree::{find_entry_in_tree, flatten_tree, object_path, read_link_target, ObjectType, Tree, TreeEntry};
+use crate::util::{get_data_hash, get_file_hash, get_str_hash, is_text, merge_lines};
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    fmt::Display,
+    fs::{self, File},
+    io::{Error, Write},
+    path::{Path, PathBuf},
+};
+
+// the commit being merged, followed by the paths left with conflicts and
+// their kind, as "<kind> <path>" lines
+const MERGE_HEAD: &str = "merge_head";
+
+// the same for a commit being reverted
+const REVERT_HEAD: &str = "revert_head";
+
+pub struct MergeResult {
+    pub tree_hash: String,
+    pub conflicts: Vec<Conflict>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum ConflictKind {
+    // lines changed on both sides, written between conflict markers
+    Text,
+    // a binary file, or a path turned into different kinds of entries, on
+    // both sides, our version is kept
+    Binary,
+    // changed on one side and deleted on the other, the changed version is kept
+    Deleted,
+}
+
+impl Display for ConflictKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            ConflictKind::Text => write!(f, "text"),
+            ConflictKind::Binary => write!(f, "binary"),
+            ConflictKind::Deleted => write!(f, "deleted"),
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct Conflict {
+    pub path: String,
+    pub kind: ConflictKind,
+}
+
+impl Conflict {
+    fn new(path: &str, kind: ConflictKind) -> Conflict {
+        Conflict { path: path.to_string(), kind }
+    }
+
+    // a state line, written by an older version when it is only a path
+    fn parse(line: &str) -> Conflict {
+        let kind = match line.split_once(' ') {
+            Some(("text", path)) => Some((ConflictKind::Text, path)),
+            Some(("binary", path)) => Some((ConflictKind::Binary, path)),
+            Some(("deleted", path)) => Some((ConflictKind::Deleted, path)),
+            _ => None,
+        };
+        match kind {
+            Some((kind, path)) => Conflict::new(path, kind),
+            None => Conflict::new(line, ConflictKind::Text),
+        }
+    }
+}
+
+/// Which version `svc resolve` takes for a conflicting path.
+#[derive(Clone, Copy, PartialEq)]
+pub enum Side {
+    Ours,
+    Theirs,
+}
+
+/// The nearest commit to `theirs` that `ours` descends from too.
+pub fn find_merge_base(commits: &[Commit], ours: &str, theirs: &str) -> Option<String> {
+    let ours = get_ancestors(commits, ours);
+    get_ancestors(commits, theirs).into_iter().find(|hash| ours.contains(hash))
+}
+
+pub fn same_entry(a: Option<&TreeEntry>, b: Option<&TreeEntry>) -> bool {
+    match (a, b) {
+        (None, None) => true,
+        (Some(a), Some(b)) => a.hash == b.hash && a.object_type == b.object_type && a.mode == b.mode,
+        _ => false,
+    }
+}
+
+/// Three-way merge of two trees against their common base. Files changed on
+/// both sides are merged line by line, conflicting lines are written between
+/// conflict markers. Other conflicts keep our version, or the one not deleted.
+pub fn merge_trees(
+    svc_path: PathBuf,
+    base_tree: Option<&str>,
+    ours_tree: &str,
+    theirs_tree: &str,
+    theirs_label: &str,
+) -> Result<MergeResult, Error> {
+    let base = match base_tree {
+        Some(base_tree) => flatten_tree(svc_path.clone(), base_tree)?,
+        None => BTreeMap::new(),
+    };
+    let ours = flatten_tree(svc_path.clone(), ours_tree)?;
+    let theirs = flatten_tree(svc_path.clone(), theirs_tree)?;
+    let paths: BTreeSet<&String> = base.keys().chain(ours.keys()).chain(theirs.keys()).collect();
+
+    let mut merged: BTreeMap<String, TreeEntry> = BTreeMap::new();
+    let mut conflicts = Vec::new();
+    for path in paths {
+        let (b, o, t) = (base.get(path), ours.get(path), theirs.get(path));
+        let entry = if same_entry(o, t) || same_entry(b, t) {
+            o.cloned()
+        } else if same_entry(b, o) {
+            t.cloned()
+        } else {
+            match (o, t) {
+                (Some(o), Some(t)) if o.object_type == t.object_type && o.hash == t.hash => {
+                    // only the modes differ
+                    Some(o.clone())
+                }
+                (Some(o), Some(t))
+                    if o.object_type == ObjectType::ObjectBlob && t.object_type == ObjectType::ObjectBlob =>
+                {
+                    let base_data = match b {
+                        Some(b) if b.object_type == ObjectType::ObjectBlob => {
+                            TreeEntry::read_blob(object_path(&svc_path, &b.hash))?
+                        }
+                        _ => Vec::new(),
+                    };
+                    let ours_data = TreeEntry::read_blob(object_path(&svc_path, &o.hash))?;
+                    let theirs_data = TreeEntry::read_blob(object_path(&svc_path, &t.hash))?;
+                    if !is_text(&base_data) || !is_text(&ours_data) || !is_text(&theirs_data) {
+                        conflicts.push(Conflict::new(path, ConflictKind::Binary));
+                        Some(o.clone())
+                    } else {
+                        let (data, conflict) = merge_lines(&base_data, &ours_data, &theirs_data, "HEAD", theirs_label);
+                        if conflict {
+                            conflicts.push(Conflict::new(path, ConflictKind::Text));
+                        }
+                        let hash = get_data_hash(&data);
+                        TreeEntry::write_blob_data(&data, svc_path.clone(), &hash)?;
+                        // a mode change on one side only is kept
+                        let mode = match b {
+                            Some(b) if b.mode == o.mode => t.mode,
+                            _ => o.mode,
+                        };
+                        Some(TreeEntry {
+                            hash,
+                            size: data.len() as u64,
+                            mode,
+                            ..o.clone()
+                        })
+                    }
+                }
+                (o, t) => {
+                    // changed on one side and deleted on the other, or of different kinds
+                    let kind = if o.is_some() && t.is_some() { ConflictKind::Binary } else { ConflictKind::Deleted };
+                    conflicts.push(Conflict::new(path, kind));
+                    o.or(t).cloned()
+                }
+            }
+        };
+        if let Some(entry) = entry {
+            merged.insert(path.clone(), entry);
+        }
+    }
+    let tree = Tree::from_entries(svc_path, &merged)?;
+    Ok(MergeResult {
+        tree_hash: tree.hash,
+        conflicts,
+    })
+}
+
+// refuse to merge over files that were modified but not saved
+fn check_clean(svc_path: PathBuf, tree_hash: &str) -> Result<(), Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    for (path, entry) in flatten_tree(svc_path, tree_hash)? {
+        let file_path = root_path.join(&path);
+        let file_hash = match fs::symlink_metadata(&file_path) {
+            Ok(meta) if meta.file_type().is_symlink() => get_str_hash(&read_link_target(file_path)),
+            Ok(meta) if meta.is_file() => get_file_hash(file_path),
+            _ => continue,
+        };
+        if entry.object_type != ObjectType::ObjectTree && file_hash != entry.hash {
+            return Err(Error::other(format!(
+                "'{}' was modified but not saved, commit it first",
+                path
+            )));
+        }
+    }
+    Ok(())
+}
+
+// refuse to write files of the new tree over untracked ones
+fn check_untracked(svc_path: PathBuf, head_tree: Option<&str>, new_tree: &str, action: &str) -> Result<(), Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    let ours = match head_tree {
+        Some(head_tree) => flatten_tree(svc_path.clone(), head_tree)?,
+        None => BTreeMap::new(),
+    };
+    for (path, entry) in flatten_tree(svc_path, new_tree)? {
+        if entry.object_type != ObjectType::ObjectTree
+            && !ours.contains_key(&path)
+            && fs::symlink_metadata(root_path.join(&path)).is_ok()
+        {
+            return Err(Error::other(format!("untracked '{}' would be overwritten by {}", path, action)));
+        }
+    }
+    Ok(())
+}
+
+/// Check that the workspace at `head_tree` can be switched to `new_tree`
+/// without losing changes or untracked files.
+pub fn check_workspace(svc_path: PathBuf, head_tree: Option<&str>, new_tree: &str, action: &str) -> Result<(), Error> {
+    if let Some(head_tree) = head_tree {
+        check_clean(svc_path.clone(), head_tree)?;
+    }
+    check_untracked(svc_path, head_tree, new_tree, action)
+}
+
+/// Merge commit `their_hash` into HEAD and commit the result, or leave the
+/// conflicts in the workspace to be fixed and committed by hand.
+/// `new_commits` are commits of the merged history missing from our log.
+pub fn merge_into_head(
+    svc_path: PathBuf,
+    their_hash: &str,
+    new_commits: Vec<Commit>,
+    theirs_label: &str,
+    message: String,
+) -> Result<(), Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    check_no_pending(svc_path.clone())?;
+    let head_hash = Commit::get_head_hash(svc_path.clone());
+    let mut commits = Commit::read_from_log(svc_path.clone());
+    let head_tree = match commits.iter().find(|commit| commit.hash == head_hash) {
+        Some(commit) => commit.tree_hash.clone(),
+        None => return Err(Error::other("no commit yet")),
+    };
+    commits.extend(new_commits.iter().cloned());
+    let their_tree = match commits.iter().find(|commit| commit.hash == their_hash) {
+        Some(commit) => commit.tree_hash.clone(),
+        None => return Err(Error::other(format!("commit {} not found", their_hash))),
+    };
+    let base_hash = find_merge_base(&commits, &head_hash, their_hash);
+    if base_hash.as_deref() == Some(their_hash) {
+        println!("already up-to-date.");
+        return Ok(());
+    }
+    check_clean(svc_path.clone(), &head_tree)?;
+
+    if base_hash.as_deref() == Some(head_hash.as_str()) {
+        // nothing to merge, just move forward
+        check_untracked(svc_path.clone(), Some(&head_tree), &their_tree, "merge")?;
+        Commit::restore_tree(root_path, svc_path.clone(), their_tree.clone())?;
+        Commit::remove_stale_files(svc_path.clone(), &head_tree, &their_tree)?;
+        Commit::remove_stale_dirs(svc_path.clone(), head_tree, their_tree)?;
+        if !new_commits.is_empty() {
+            // like a commit, the new commits replace the ones after HEAD
+            Commit::check_and_update_latest(svc_path.clone());
+            let mut log = Commit::read_from_log(svc_path.clone());
+            log.extend(new_commits);
+            Commit::write_log(&log, svc_path.clone())?;
+            fs::write(svc_path.join("latest"), their_hash)?;
+        }
+        Commit::reset_head(svc_path, their_hash.to_string());
+        println!("fast-forward to commit {}.", their_hash);
+        return Ok(());
+    }
+
+    let base_tree = base_hash.and_then(|hash| commits.iter().find(|commit| commit.hash == hash).map(|commit| commit.tree_hash.clone()));
+    let result = merge_trees(svc_path.clone(), base_tree.as_deref(), &head_tree, &their_tree, theirs_label)?;
+    check_untracked(svc_path.clone(), Some(&head_tree), &result.tree_hash, "merge")?;
+
+    // like a commit, merging discards the commits after HEAD
+    Commit::check_and_update_latest(svc_path.clone());
+    if !new_commits.is_empty() {
+        // keep HEAD the last commit of the log
+        let mut log = Commit::read_from_log(svc_path.clone());
+        let head = log.pop().unwrap();
+        log.extend(new_commits);
+        log.push(head);
+        Commit::write_log(&log, svc_path.clone())?;
+    }
+    Commit::restore_tree(root_path, svc_path.clone(), result.tree_hash.clone())?;
+    Commit::remove_stale_files(svc_path.clone(), &head_tree, &result.tree_hash)?;
+    Commit::remove_stale_dirs(svc_path.clone(), head_tree, result.tree_hash.clone())?;
+
+    if !result.conflicts.is_empty() {
+        write_state(svc_path.join(MERGE_HEAD), their_hash, &result.conflicts)?;
+        print_conflicts(&result.conflicts);
+        println!("automatic merge failed, fix the conflicts and run \"svc commit\" to finish the merge.");
+        return Ok(());
+    }
+    let commit = Commit::from_tree(message, format!("{},{}", head_hash, their_hash), result.tree_hash);
+    Commit::write_to_log(&commit, svc_path.clone())?;
+    fs::write(svc_path.join("latest"), &commit.hash)?;
+    Commit::reset_head(svc_path.clone(), commit.hash.clone());
+    RepoMeta::touch_updated_at(svc_path)?;
+    println!("merge {} into commit {}.", theirs_label, commit.hash);
+    Ok(())
+}
+
+/// Undo the changes of a commit with a new commit on top of HEAD, or leave
+/// the conflicts in the workspace to be fixed and committed by hand.
+pub fn revert_commit(svc_path: PathBuf, target: &Commit, message: Option<String>) -> Result<(), Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    check_no_pending(svc_path.clone())?;
+    let head = Commit::find_commit(svc_path.clone(), "HEAD").map_err(|_| Error::other("no commit yet"))?;
+    let parent_tree = match target.parents()[..] {
+        [] => Tree::from_entries(svc_path.clone(), &BTreeMap::new())?.hash,
+        [ref parent] => match get_tree_of_commit(svc_path.clone(), parent.clone()) {
+            Ok(tree_hash) => tree_hash,
+            Err(_) => return Err(Error::other(format!("the parent of commit {} is not in the log", target.hash))),
+        },
+        _ => return Err(Error::other(format!("commit {} is a merge and can't be reverted", target.hash))),
+    };
+    check_clean(svc_path.clone(), &head.tree_hash)?;
+
+    // merging the parent with the commit as base takes its changes back
+    let label = format!("parent of {}", &target.hash[..8]);
+    let result = merge_trees(svc_path.clone(), Some(&target.tree_hash), &head.tree_hash, &parent_tree, &label)?;
+    if result.tree_hash == head.tree_hash {
+        println!("nothing to revert.");
+        return Ok(());
+    }
+    check_untracked(svc_path.clone(), Some(&head.tree_hash), &result.tree_hash, "revert")?;
+
+    // like a commit, reverting discards the commits after HEAD
+    Commit::check_and_update_latest(svc_path.clone());
+    Commit::restore_tree(root_path, svc_path.clone(), result.tree_hash.clone())?;
+    Commit::remove_stale_files(svc_path.clone(), &head.tree_hash, &result.tree_hash)?;
+    Commit::remove_stale_dirs(svc_path.clone(), head.tree_hash.clone(), result.tree_hash.clone())?;
+
+    if !result.conflicts.is_empty() {
+        write_state(svc_path.join(REVERT_HEAD), &target.hash, &result.conflicts)?;
+        print_conflicts(&result.conflicts);
+        println!("automatic revert failed, fix the conflicts and run \"svc commit\" to finish the revert.");
+        return Ok(());
+    }
+    let message = message.unwrap_or_else(|| {
+        format!("revert \"{}\"\n\nthis reverts commit {}.", target.message.lines().next().unwrap_or(""), target.hash)
+    });
+    let commit = Commit::from_tree(message, head.hash, result.tree_hash);
+    Commit::write_to_log(&commit, svc_path.clone())?;
+    fs::write(svc_path.join("latest"), &commit.hash)?;
+    Commit::reset_head(svc_path.clone(), commit.hash.clone());
+    RepoMeta::touch_updated_at(svc_path)?;
+    println!("revert commit {} as commit {}.", target.hash, commit.hash);
+    Ok(())
+}
+
+// refuse to start over a merge or revert waiting to be committed
+pub fn check_no_pending(svc_path: PathBuf) -> Result<(), Error> {
+    if read_merge_state(svc_path.clone()).is_some() {
+        return Err(Error::other("a merge is in progress, fix the conflicts and run \"svc commit\" first"));
+    }
+    if read_revert_state(svc_path).is_some() {
+        return Err(Error::other("a revert is in progress, fix the conflicts and run \"svc commit\" first"));
+    }
+    Ok(())
+}
+
+fn write_state(state_path: PathBuf, hash: &str, conflicts: &[Conflict]) -> Result<(), Error> {
+    let mut file = File::create(state_path)?;
+    file.write_fmt(format_args!("{}\n", hash))?;
+    for conflict in conflicts {
+        file.write_fmt(format_args!("{} {}\n", conflict.kind, conflict.path))?;
+    }
+    Ok(())
+}
+
+fn read_state(state_path: PathBuf) -> Option<(String, Vec<Conflict>)> {
+    let data = fs::read_to_string(state_path).ok()?;
+    let mut lines = data.lines();
+    let hash = lines.next()?.to_string();
+    Some((hash, lines.map(Conflict::parse).collect()))
+}
+
+fn print_conflicts(conflicts: &[Conflict]) {
+    for conflict in conflicts {
+        println!("conflict ({}): {}", conflict.kind, conflict.path);
+    }
+}
+
+/// The commit being merged and the paths that had conflicts, if a merge is
+/// waiting to be committed.
+pub fn read_merge_state(svc_path: PathBuf) -> Option<(String, Vec<Conflict>)> {
+    read_state(svc_path.join(MERGE_HEAD))
+}
+
+/// The same for a revert.
+pub fn read_revert_state(svc_path: PathBuf) -> Option<(String, Vec<Conflict>)> {
+    read_state(svc_path.join(REVERT_HEAD))
+}
+
+// forget a merge or revert once it is committed
+pub fn clear_merge_state(svc_path: PathBuf) -> Result<(), Error> {
+    for state in [MERGE_HEAD, REVERT_HEAD] {
+        match fs::remove_file(svc_path.join(state)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+            _ => (),
+        }
+    }
+    Ok(())
+}
+
+/// Conflicts of a merge that are not resolved yet: text files still holding
+/// conflict markers, and the other kinds until a version is chosen with
+/// `resolve_conflicts`.
+pub fn unresolved_conflicts(svc_path: PathBuf, conflicts: &[Conflict]) -> Vec<Conflict> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    conflicts
+        .iter()
+        .filter(|conflict| conflict.kind != ConflictKind::Text || has_markers(&root_path.join(&conflict.path)))
+        .cloned()
+        .collect()
+}
+
+fn has_markers(file_path: &Path) -> bool {
+    match fs::read(file_path) {
+        Ok(data) => {
+            let data = String::from_utf8_lossy(&data);
+            data.lines().any(|line| line.starts_with("<<<<<<< "))
+                && data.lines().any(|line| line.starts_with(">>>>>>> "))
+        }
+        Err(_) => false,
+    }
+}
+
+/// Mark conflicting paths, relative to the root, of a pending merge or revert
+/// as resolved, taking our or their version of them when a side is given.
+pub fn resolve_conflicts(svc_path: PathBuf, paths: &[String], side: Option<Side>) -> Result<(), Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    let (state_path, hash, mut conflicts) = match (read_merge_state(svc_path.clone()), read_revert_state(svc_path.clone())) {
+        (Some((hash, conflicts)), _) => (svc_path.join(MERGE_HEAD), hash, conflicts),
+        (None, Some((hash, conflicts))) => (svc_path.join(REVERT_HEAD), hash, conflicts),
+        (None, None) => return Err(Error::other("no merge or revert in progress")),
+    };
+    let side_tree = match side {
+        Some(Side::Ours) => Some(get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone()))),
+        // their side of a revert is the parent of the reverted commit
+        Some(Side::Theirs) if state_path.ends_with(REVERT_HEAD) => {
+            let target = Commit::find_commit(svc_path.clone(), &hash).map_err(Error::other)?;
+            Some(match target.parents().first() {
+                Some(parent) => get_tree_of_commit(svc_path.clone(), parent.clone()),
+                None => Ok(Tree::from_entries(svc_path.clone(), &BTreeMap::new())?.hash),
+            })
+        }
+        Some(Side::Theirs) => Some(get_tree_of_commit(svc_path.clone(), hash.clone())),
+        None => None,
+    };
+    let side_tree = match side_tree {
+        Some(Ok(tree_hash)) => Some(tree_hash),
+        Some(Err(_)) => return Err(Error::other("the version to take is not in the log")),
+        None => None,
+    };
+
+    // check every path first, so nothing is changed when one is wrong
+    for path in paths {
+        if !conflicts.iter().any(|conflict| conflict.path == *path) {
+            return Err(Error::other(format!("'{}' has no conflict", path)));
+        }
+        if side_tree.is_none() && has_markers(&root_path.join(path)) {
+            return Err(Error::other(format!("'{}' still has conflict markers", path)));
+        }
+    }
+    if let Some(side_tree) = side_tree {
+        for path in paths {
+            match find_entry_in_tree(&svc_path, &side_tree, path)? {
+                Some(entry) => Commit::restore_path(svc_path.clone(), path, entry, None)?,
+                None => match fs::remove_file(root_path.join(path)) {
+                    Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+                    _ => (),
+                },
+            }
+        }
+    }
+    conflicts.retain(|conflict| !paths.contains(&conflict.path));
+    write_state(state_path, &hash, &conflicts)?;
+    for path in paths {
+        println!("resolve '{}'.", path);
+    }
+    Ok(())
+}
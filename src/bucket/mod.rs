@@ -1,19 +1,37 @@
-use std::{env, fs::{self, File}, io::{self, Write}, process, path::PathBuf};
-use chrono::Local;
-use repo::{check_svc_repo, RepoMeta};
-use log::{Commit, check_blob_state, get_file_paths_in_dir};
+use crate::util::{get_data_hash, get_file_hash, get_str_hash};
+use std::{collections::{BTreeMap, BTreeSet}, env, fs::{self, File, OpenOptions}, io::{self, Write}, process, path::{Component, Path, PathBuf}};
+use archive::write_archive;
+use tree::{find_entry_in_tree, find_object, flatten_tree, read_link_target, read_object};
+use stash::{drop_stash, get_stash, pop_stash, push_stash, read_stash};
+use merge::{check_no_pending, clear_merge_state, read_merge_state, read_revert_state, resolve_conflicts, revert_commit, same_entry, unresolved_conflicts, ConflictKind, Side};
+use log::{check_blob_state, current_date, get_blob_hash_from_entry, get_dirs_in_tree, get_empty_dirs_in_dir, get_file_paths_in_dir, AMENDED};
 
 mod repo;
 mod log;
 mod tree;
+mod ignore;
+mod merge;
+mod archive;
+mod stash;
+
+pub use archive::ArchiveFormat;
+pub use ignore::Ignore;
+pub use merge::{check_workspace, merge_into_head};
+pub use repo::{check_svc_repo, create_svc_repo, read_remote_log, write_remote_log, RepoMeta, DEFAULT_REMOTE};
+pub use log::{get_ancestors, get_tree_of_commit, parse_date, Commit, NO_PARENT};
+pub use tree::{check_object, check_tree_hash, object_path, ObjectType, Tree, TreeEntry, MODE_DIR, MODE_FILE, MODE_LINK};
 
 pub fn info() {
     // println!("command info");
     match check_svc_repo() {
         Ok(svc_path) => {
-            let repo_meta = RepoMeta::read_repo_meta(svc_path);
+            let repo_meta = RepoMeta::read_repo_meta(svc_path.clone());
             println!("-----------------------------------------");
             println!("{}", repo_meta);
+            if let Some(state) = get_remote_state(svc_path) {
+                println!("-----------------------------------------");
+                println!("{}", state);
+            }
             println!("-----------------------------------------");
             println!("notice: you can edit .svc/repo to set metadata mannually.")
         }
@@ -34,18 +52,13 @@ pub fn init() {
         Err(_) => {
             println!("-----------------------------------------");
             let current_dir = env::current_dir().unwrap();
-            fs::create_dir(current_dir.join(".svc")).unwrap();
-            println!("notice: .svc create successfully.");
-            fs::create_dir(current_dir.join(".svc/objects")).unwrap();
-            println!("notice: .svc/objects create successfully.");
-            fs::File::create(current_dir.join(".svc/repo")).unwrap();
-            println!("notice: .svc/repo create successfully.");
-            fs::File::create(current_dir.join(".svc/head")).unwrap();
-            println!("notice: .svc/head create successfully.");
-            fs::File::create(current_dir.join(".svc/log")).unwrap();
-            println!("notice: .svc/log create successfully.");
-            fs::File::create(current_dir.join(".svc/latest")).unwrap();
-            println!("notice: .svc/latest create successfully.");
+            if let Err(err) = create_svc_repo(current_dir.clone()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            for name in [".svc", ".svc/objects", ".svc/repo", ".svc/head", ".svc/log", ".svc/latest"] {
+                println!("notice: {} create successfully.", name);
+            }
             println!("-----------------------------------------");
             
             let mut repo_name = String::from("");
@@ -79,17 +92,58 @@ pub fn log() {
         Ok(svc_path) => {
             let head_hash = Commit::get_head_hash(svc_path.clone());
             let commits = Commit::read_from_log(svc_path);
-            if commits.len() == 0 {
+            if commits.is_empty() {
                 eprintln!("error: no commit yet");
             }
             for commit in commits.iter().rev() {
-                if commit.hash == head_hash {
-                    println!("commit {} (HEAD)", commit.hash);
-                } else {
-                    println!("commit {}", commit.hash);
+                print_commit(commit, &head_hash);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn print_commit(commit: &Commit, head_hash: &str) {
+    if commit.hash == head_hash {
+        println!("commit {} (HEAD)", commit.hash);
+    } else {
+        println!("commit {}", commit.hash);
+    }
+    let parents = commit.parents();
+    if parents.len() > 1 {
+        println!("Merge: {}", parents.join(" "));
+    }
+    if !commit.author.is_empty() {
+        println!("Author: {}", commit.author);
+    }
+    println!("Date:  {}", commit.date);
+    println!("\n\t{}\n", commit.message.replace('\n', "\n\t"));
+}
+
+pub fn show(object: &str) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            // "<version>:<path>" is a file of a version, "<version>" the commit itself
+            if let Some((version, path)) = object.split_once(':') {
+                cat_file(svc_path, version, path);
+                return;
+            }
+            let commit = match Commit::find_commit(svc_path.clone(), object) {
+                Ok(commit) => commit,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
                 }
-                println!("Date:  {}", commit.date);
-                println!("\n\t{}\n", commit.message);
+            };
+            print_commit(&commit, &Commit::get_head_hash(svc_path.clone()));
+            // changes are against the first parent, like the log shows them
+            let parent_tree = commit.parents().first().and_then(|parent| get_tree_of_commit(svc_path.clone(), parent.clone()).ok());
+            if let Err(err) = print_changes(svc_path, parent_tree.as_deref(), &commit.tree_hash) {
+                eprintln!("error: {}", err);
+                process::exit(1);
             }
         }
         Err(err) => {
@@ -99,15 +153,90 @@ pub fn log() {
     }
 }
 
+// list the paths added (A), modified (M) or deleted (D) between two trees
+fn print_changes(svc_path: PathBuf, old_tree: Option<&str>, new_tree: &str) -> io::Result<()> {
+    let old_entries = match old_tree {
+        Some(old_tree) => flatten_tree(svc_path.clone(), old_tree)?,
+        None => BTreeMap::new(),
+    };
+    let new_entries = flatten_tree(svc_path, new_tree)?;
+    let paths: BTreeSet<&String> = old_entries.keys().chain(new_entries.keys()).collect();
+    for path in paths {
+        let (old, new) = (old_entries.get(path), new_entries.get(path));
+        if same_entry(old, new) {
+            continue;
+        }
+        let (state, entry, others) = match (old, new) {
+            (None, Some(entry)) => ("A", entry, &old_entries),
+            (Some(entry), None) => ("D", entry, &new_entries),
+            (_, Some(entry)) => ("M", entry, &old_entries),
+            (None, None) => unreachable!(),
+        };
+        if entry.object_type == ObjectType::ObjectTree {
+            // an empty directory that got files, or lost them, is no change of its own
+            let prefix = format!("{}/", path);
+            if others.range(prefix.clone()..).next().is_some_and(|(other, _)| other.starts_with(&prefix)) {
+                continue;
+            }
+            println!("{}  {}", state, prefix);
+        } else {
+            println!("{}  {}", state, path);
+        }
+    }
+    Ok(())
+}
+
+pub fn cat(path: &str, version: &str) {
+    match check_svc_repo() {
+        Ok(svc_path) => cat_file(svc_path, version, path),
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+// write a file of a version to stdout as it was saved, a link gives its target
+fn cat_file(svc_path: PathBuf, version: &str, path: &str) {
+    let version = if version.is_empty() { "HEAD" } else { version };
+    let target = match Commit::find_commit(svc_path.clone(), version) {
+        Ok(target) => target,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1)
+        }
+    };
+    let relative_path = match workspace_path(svc_path.parent().unwrap(), path) {
+        Some(relative_path) => PathBuf::from(relative_path),
+        None => {
+            eprintln!("error: '{}' is outside repository", path);
+            process::exit(1);
+        }
+    };
+    let components = relative_path.components().collect();
+    let data = match get_blob_hash_from_entry(svc_path.clone(), target.tree_hash, components) {
+        Ok((hash, _)) => TreeEntry::read_blob(object_path(&svc_path, &hash)),
+        Err(_) => {
+            eprintln!("error: '{}' is not a file of commit {}", path, target.hash);
+            process::exit(1);
+        }
+    };
+    if let Err(err) = data.and_then(|data| io::stdout().lock().write_all(&data)) {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
 pub fn status() {
     match check_svc_repo() {
         Ok(svc_path) => {
             let mut untracked: Vec<PathBuf> = Vec::new();
             let mut modified: Vec<PathBuf> = Vec::new();
+            let mut mode_changed: Vec<PathBuf> = Vec::new();
             let root_path = svc_path.clone().parent().unwrap().to_path_buf();
             let mut files: Vec<PathBuf> = Vec::new();
             let exclude = Commit::read_ignore(svc_path.clone());
-            files = get_file_paths_in_dir(root_path, &mut files, exclude).to_vec();
+            files = get_file_paths_in_dir(root_path.clone(), &mut files, &exclude).to_vec();
             for file_path in files {
                 // println!("{:?}", file_path);
                 if let Err(err) = check_blob_state(file_path.clone(), svc_path.clone()) {
@@ -115,27 +244,86 @@ pub fn status() {
                         untracked.push(file_path.clone());
                     } else if err == "doesn't match" {
                         modified.push(file_path);
+                    } else if err == "mode changed" {
+                        mode_changed.push(file_path);
                     }
                 }
             }
-            if modified.len() == 0 && untracked.len() == 0 {
+            let mut empty_dirs: Vec<PathBuf> = Vec::new();
+            get_empty_dirs_in_dir(root_path.clone(), &mut empty_dirs, &exclude);
+            let mut tracked_dirs: Vec<(PathBuf, bool)> = Vec::new();
+            if let Ok(tree_hash) = get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone())) {
+                if let Err(err) = get_dirs_in_tree(svc_path.clone(), tree_hash, root_path, &mut tracked_dirs) {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+            let added_dirs: Vec<PathBuf> = empty_dirs
+                .into_iter()
+                .filter(|dir| !tracked_dirs.iter().any(|(tracked, _)| tracked == dir))
+                .collect();
+            let removed_dirs: Vec<PathBuf> = tracked_dirs
+                .into_iter()
+                .filter(|(dir, is_empty)| *is_empty && !dir.is_dir())
+                .map(|(dir, _)| dir)
+                .collect();
+            if let Some(state) = get_remote_state(svc_path.clone()) {
+                println!("{}", state);
+            }
+            let pending = match (read_merge_state(svc_path.clone()), read_revert_state(svc_path.clone())) {
+                (Some((their_hash, conflicts)), _) => Some(("merge", their_hash, conflicts)),
+                (None, Some((target_hash, conflicts))) => Some(("revert", target_hash, conflicts)),
+                (None, None) => None,
+            };
+            if let Some((action, hash, conflicts)) = pending {
+                println!("{} commit {}.", if action == "merge" { "merging" } else { "reverting" }, hash);
+                let conflicts = unresolved_conflicts(svc_path.clone(), &conflicts);
+                if !conflicts.is_empty() {
+                    println!("\nunresolved conflicts:");
+                    println!("  (fix them and run \"svc commit\" to finish the {})", action);
+                    println!("  (use \"svc resolve --ours|--theirs <path>\" to take one version)");
+                    for conflict in conflicts {
+                        println!("  {} ({})", conflict.path, conflict.kind);
+                    }
+                }
+            }
+            if modified.is_empty() && mode_changed.is_empty() && untracked.is_empty()
+                && added_dirs.is_empty() && removed_dirs.is_empty() {
                 println!("clean workspace.");
                 return;
             }
-            if modified.len() != 0 {
+            if !modified.is_empty() {
                 println!("\nmodified but not saved:");
                 println!("  (run \"svc checkout\" will get an error)");
                 for file in modified {
                     println!("  {}", file.to_str().unwrap());
                 }
             }
-            if untracked.len() != 0 {
+            if !mode_changed.is_empty() {
+                println!("\nmode changed:");
+                for file in mode_changed {
+                    println!("  {}", file.to_str().unwrap());
+                }
+            }
+            if !untracked.is_empty() {
                 println!("\nunntracked:");
                 println!("  (run \"svc commmit\" will discard commits after HEAD)");
                 for file in untracked {
                     println!("  {}", file.to_str().unwrap());
                 }
             }
+            if !added_dirs.is_empty() {
+                println!("\nnew empty directories:");
+                for dir in added_dirs {
+                    println!("  {}/", dir.to_str().unwrap());
+                }
+            }
+            if !removed_dirs.is_empty() {
+                println!("\nremoved empty directories:");
+                for dir in removed_dirs {
+                    println!("  {}/", dir.to_str().unwrap());
+                }
+            }
             println!("\nnotice: run \"svc commit\" to save current workspace");
         }
         Err(err) => {
@@ -145,28 +333,69 @@ pub fn status() {
     }
 }
 
-pub fn commit(message: &String) {
+pub fn commit(message: &Option<String>, amend: bool, paths: &[String]) {
     // println!("command commit");
     match check_svc_repo() {
         Ok(svc_path) => {
+            let root_path = svc_path.parent().unwrap().to_path_buf();
+            let mut relative_paths = Vec::new();
+            for path in paths {
+                match workspace_path(&root_path, path) {
+                    Some(relative_path) => relative_paths.push(relative_path),
+                    None => {
+                        eprintln!("error: '{}' is outside repository", path);
+                        process::exit(1);
+                    }
+                }
+            }
+            if amend {
+                if let Err(err) = amend_head(svc_path, message.clone(), &relative_paths) {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+                return;
+            }
+            let merge_state = read_merge_state(svc_path.clone());
+            let revert_state = read_revert_state(svc_path.clone());
+            if !paths.is_empty() && (merge_state.is_some() || revert_state.is_some()) {
+                eprintln!("error: a merge or revert is in progress, commit the whole workspace to finish it.");
+                process::exit(1);
+            }
+            if let Some((_, conflicts)) = merge_state.as_ref().or(revert_state.as_ref()) {
+                let conflicts = unresolved_conflicts(svc_path.clone(), conflicts);
+                if !conflicts.is_empty() {
+                    for conflict in conflicts {
+                        match conflict.kind {
+                            ConflictKind::Text => eprintln!("error: '{}' still has conflict markers.", conflict.path),
+                            kind => eprintln!("error: '{}' has a {} conflict, take a version with \"svc resolve\".", conflict.path, kind),
+                        }
+                    }
+                    process::exit(1);
+                }
+            }
             Commit::check_and_update_latest(svc_path.clone());
-            let commit = Commit::new(message.to_string(), svc_path.clone());
+            let merge_hash = merge_state.as_ref().map(|(their_hash, _)| their_hash.as_str());
+            let head_tree = get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone())).ok();
+            let tree_hash = match save_commit_tree(svc_path.clone(), head_tree.as_deref(), &relative_paths) {
+                Ok(tree_hash) => tree_hash,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            };
+            let commit = Commit::new(message.clone().unwrap_or_default(), svc_path.clone(), merge_hash, tree_hash);
             let mut file_latest = File::create(svc_path.join("latest")).unwrap();
-            file_latest.write(commit.hash.as_bytes()).unwrap();
+            file_latest.write_all(commit.hash.as_bytes()).unwrap();
             if let Err(err) = Commit::write_to_log(&commit, svc_path.clone()) {
                 eprintln!("error: {}", err);
                 process::exit(1);
             }
             let mut file = File::create(svc_path.clone().join("head")).unwrap();
-            file.write(commit.hash.as_bytes()).unwrap();
+            file.write_all(commit.hash.as_bytes()).unwrap();
 
-            if let Err(err) = RepoMeta::update_repo_meta(RepoMeta{
-                repo_name: "\n".to_string(),
-                repo_intro: "\n".to_string(),
-                remote_url: "\n".to_string(),
-                created_at: "\n".to_string(),
-                updated_at: Local::now().format("%Y-%m-%d %H:%M").to_string()
-            }, svc_path) {
+            if let Err(err) = clear_merge_state(svc_path.clone())
+                .and_then(|_| RepoMeta::touch_updated_at(svc_path))
+            {
                 eprintln!("error: {}", err);
                 process::exit(1);
             }
@@ -179,39 +408,566 @@ pub fn commit(message: &String) {
     }
 }
 
-pub fn checkout(version: &String) {
+// the whole workspace, or only the given paths over the HEAD tree
+fn save_commit_tree(svc_path: PathBuf, head_tree: Option<&str>, paths: &[String]) -> io::Result<String> {
+    if paths.is_empty() {
+        return Ok(Commit::save_workspace(svc_path));
+    }
+    Commit::save_paths(svc_path, head_tree, paths)
+}
+
+// replace HEAD with a commit of the workspace on the same parents, the old
+// commit is appended to .svc/amended so it can still be found
+fn amend_head(svc_path: PathBuf, message: Option<String>, paths: &[String]) -> io::Result<()> {
+    check_no_pending(svc_path.clone())?;
+    let head = Commit::find_commit(svc_path.clone(), "HEAD").map_err(|_| io::Error::other("no commit to amend"))?;
+    // like a commit, amending discards the commits after HEAD
+    Commit::check_and_update_latest(svc_path.clone());
+    let message = message.unwrap_or(head.message.clone());
+    let tree_hash = save_commit_tree(svc_path.clone(), Some(&head.tree_hash), paths)?;
+    let commit = Commit::with_author(message, head.parent_hash.clone(), tree_hash, current_date(), head.author.clone());
+
+    let mut commits = Commit::read_from_log(svc_path.clone());
+    for line in commits.iter_mut().filter(|line| line.hash == head.hash) {
+        *line = commit.clone();
+    }
+    Commit::write_log(&commits, svc_path.clone())?;
+    let mut amended = OpenOptions::new().create(true).append(true).open(svc_path.join(AMENDED))?;
+    amended.write_all(head.to_log_line().as_bytes())?;
+    fs::write(svc_path.join("latest"), &commit.hash)?;
+    Commit::reset_head(svc_path.clone(), commit.hash.clone());
+    RepoMeta::touch_updated_at(svc_path)?;
+    println!("replace commit {} with commit {}, the old one is kept in .svc/{}.", head.hash, commit.hash, AMENDED);
+    Ok(())
+}
+
+pub fn checkout(version: &str) {
     // println!("Command checkout");
     // println!("{}", version);
     match check_svc_repo() {
         Ok(svc_path) => {
-            let commits = Commit::read_from_log(svc_path.clone());
-            let mut target: Option<Commit> = None;
-            let mut target_cnt = 0;
-            for commit in commits {
-                if commit.hash.starts_with(&version.to_string()) {
-                    target = Some(commit);
-                    target_cnt += 1
+            if let Err(err) = check_no_pending(svc_path.clone()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            let target = match Commit::find_logged_commit(svc_path.clone(), version) {
+                Ok(target) => target,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            let target_hash = target.hash;
+            let head_tree = get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone()));
+            if let Err(err) = Commit::restore_tree(svc_path.parent().unwrap().to_path_buf(), svc_path.clone(), target.tree_hash.clone()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            } else {
+                if let Ok(head_tree) = head_tree {
+                    if let Err(err) = Commit::remove_stale_dirs(svc_path.clone(), head_tree, target.tree_hash) {
+                        eprintln!("error: {}", err);
+                        process::exit(1);
+                    }
                 }
+                Commit::reset_head(svc_path, target_hash.clone());
+                println!("switch to commit {}.", target_hash);
             }
-            
-            if target_cnt == 1 {
-                let target = target.unwrap();
-                let target_hash = target.hash;
-                if let Err(err) = Commit::restore_tree(svc_path.parent().unwrap().to_path_buf(), svc_path.clone(), target.tree_hash) {
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn merge(version: &str, message: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let target = match Commit::find_logged_commit(svc_path.clone(), version) {
+                Ok(target) => target,
+                Err(err) => {
                     eprintln!("error: {}", err);
-                    process::exit(1);
+                    process::exit(1)
+                }
+            };
+            let message = message.clone().unwrap_or(format!("merge {}", version));
+            if let Err(err) = merge_into_head(svc_path, &target.hash, Vec::new(), version, message) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn restore(source: &Option<String>, paths: &[String]) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let target = match Commit::find_commit(svc_path.clone(), source.as_deref().unwrap_or("HEAD")) {
+                Ok(target) => target,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            let root_path = svc_path.parent().unwrap().to_path_buf();
+            // look every path up first, so nothing is restored when one is wrong
+            let mut entries = Vec::new();
+            for path in paths {
+                let relative_path = match workspace_path(&root_path, path) {
+                    Some(relative_path) => relative_path,
+                    None => {
+                        eprintln!("error: '{}' is outside repository", path);
+                        process::exit(1);
+                    }
+                };
+                let entry = if relative_path.is_empty() {
+                    Ok(Some(TreeEntry::root(&target.tree_hash)))
                 } else {
-                    Commit::reset_head(svc_path, target_hash.clone());
-                    println!("switch to commit {}.", target_hash);
+                    find_entry_in_tree(&svc_path, &target.tree_hash, &relative_path)
+                };
+                match entry {
+                    Ok(Some(entry)) => entries.push((relative_path, entry)),
+                    Err(err) => {
+                        eprintln!("error: {}", err);
+                        process::exit(1);
+                    }
+                    Ok(None) => {
+                        eprintln!("error: '{}' is not in commit {}", path, target.hash);
+                        process::exit(1);
+                    }
                 }
-            } else if target_cnt < 1 {
-                eprintln!("error: version not found.");
-                process::exit(1)
-            } else if target_cnt > 1 {
-                eprintln!("error: found more than one version matches.");
+            }
+            let head_tree = get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone())).ok();
+            for (relative_path, entry) in entries {
+                if let Err(err) = Commit::restore_path(svc_path.clone(), &relative_path, entry, head_tree.as_deref()) {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+            println!("restore {} path(s) from commit {}.", paths.len(), target.hash);
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn resolve(ours: bool, theirs: bool, paths: &[String]) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let root_path = svc_path.parent().unwrap().to_path_buf();
+            let mut relative_paths = Vec::new();
+            for path in paths {
+                match workspace_path(&root_path, path) {
+                    Some(relative_path) => relative_paths.push(relative_path),
+                    None => {
+                        eprintln!("error: '{}' is outside repository", path);
+                        process::exit(1);
+                    }
+                }
+            }
+            let side = match (ours, theirs) {
+                (true, _) => Some(Side::Ours),
+                (_, true) => Some(Side::Theirs),
+                _ => None,
+            };
+            if let Err(err) = resolve_conflicts(svc_path, &relative_paths, side) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn export(version: &str, dir: &str, paths: &[String]) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let target = match Commit::find_commit(svc_path.clone(), version) {
+                Ok(target) => target,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            let dir = env::current_dir().unwrap().join(dir);
+            if let Ok(mut entries) = fs::read_dir(&dir) {
+                if entries.next().is_some() {
+                    eprintln!("error: '{}' already exists and is not empty", dir.to_str().unwrap());
+                    process::exit(1);
+                }
+            }
+            if let Err(err) = export_paths(svc_path, &target.tree_hash, dir.clone(), paths) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+            println!("export commit {} into '{}'.", target.hash, dir.to_str().unwrap());
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+// restore a whole tree, or only some paths of it, into `dir`
+fn export_paths(svc_path: PathBuf, tree_hash: &str, dir: PathBuf, paths: &[String]) -> io::Result<()> {
+    let mut entries = Vec::new();
+    for path in paths {
+        let path = path.trim_start_matches("./").trim_end_matches('/');
+        match find_entry_in_tree(&svc_path, tree_hash, path)? {
+            Some(entry) => entries.push((path, entry)),
+            None => return Err(io::Error::other(format!("'{}' is not in this version", path))),
+        }
+    }
+    fs::create_dir_all(&dir)?;
+    if paths.is_empty() {
+        return Commit::restore_tree_into(dir.clone(), dir, svc_path, tree_hash.to_string());
+    }
+    for (path, entry) in entries {
+        let entry_path = dir.join(path);
+        fs::create_dir_all(entry_path.parent().unwrap())?;
+        let object_path = object_path(&svc_path, &entry.hash);
+        match entry.object_type {
+            ObjectType::ObjectTree => {
+                fs::create_dir_all(&entry_path)?;
+                Commit::restore_tree_into(dir.clone(), entry_path, svc_path.clone(), entry.hash)?;
+            }
+            ObjectType::ObjectLink => TreeEntry::restore_link(entry_path, object_path)?,
+            ObjectType::ObjectBlob => TreeEntry::restore_blob(entry_path, object_path, entry.mode)?,
+        }
+    }
+    Ok(())
+}
+
+pub fn revert(version: &str, message: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let target = match Commit::find_commit(svc_path.clone(), version) {
+                Ok(target) => target,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            if let Err(err) = revert_commit(svc_path, &target, message.clone()) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn stash_push(message: &Option<String>, include_untracked: bool) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = push_stash(svc_path, message.clone(), include_untracked) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn stash_pop(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = pop_stash(svc_path, name) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn stash_list() {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            for (index, entry) in read_stash(svc_path).iter().rev().enumerate() {
+                println!("stash@{{{}}}: {} ({})", index, entry.message.lines().next().unwrap_or(""), entry.date);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn stash_drop(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = drop_stash(svc_path, name) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn stash_show(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let (index, entry) = match get_stash(svc_path.clone(), name) {
+                Ok(stash) => stash,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            println!("stash@{{{}}}: {} ({})", index, entry.message.lines().next().unwrap_or(""), entry.date);
+            let base_tree = get_tree_of_commit(svc_path.clone(), entry.parent_hash.clone()).ok();
+            if let Err(err) = print_changes(svc_path, base_tree.as_deref(), &entry.tree_hash) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn archive(version: &str, format: &Option<String>, output: &str, prefix: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let target = match Commit::find_commit(svc_path.clone(), version) {
+                Ok(target) => target,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            let format = match format {
+                Some(format) => ArchiveFormat::parse(format),
+                None => ArchiveFormat::from_file_name(output).or(Some(ArchiveFormat::Tar)),
+            };
+            let format = match format {
+                Some(format) => format,
+                None => {
+                    eprintln!("error: unknown archive format, use tar, tar.gz or zip.");
+                    process::exit(1)
+                }
+            };
+            let prefix = prefix.clone().unwrap_or_default();
+            match write_archive(svc_path, &target.tree_hash, &target.date, format, &PathBuf::from(output), &prefix) {
+                Ok(count) => println!("archive {} file(s) of commit {} into '{}'.", count, target.hash, output),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn cat_object(hash: &str, show_type: bool, size: bool, pretty: bool) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let hash = match find_object(&svc_path, hash) {
+                Ok(hash) => hash,
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1)
+                }
+            };
+            let (object_type, data) = match read_object(&svc_path, &hash) {
+                Ok(object) => object,
+                Err(err) => {
+                    eprintln!("error: object {} can't be read: {}", hash, err);
+                    process::exit(1)
+                }
+            };
+            let valid = match object_type {
+                ObjectType::ObjectTree => check_tree_hash(&hash, &String::from_utf8_lossy(&data)),
+                _ => get_data_hash(&data) == hash,
+            };
+            if !valid {
+                eprintln!("warning: object {} doesn't match its hash.", hash);
+            }
+            if show_type {
+                println!("{}", object_type);
+            } else if size {
+                println!("{}", data.len());
+            } else if pretty && object_type == ObjectType::ObjectTree {
+                if let Err(err) = print_tree(&svc_path, &hash, "", false) {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            } else if let Err(err) = io::stdout().lock().write_all(&data) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn ls_tree(object: &str, path: &Option<String>, recursive: bool) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            // a version names its tree, otherwise it has to be a tree hash
+            let tree_hash = match Commit::find_commit(svc_path.clone(), object) {
+                Ok(commit) => commit.tree_hash,
+                Err(_) => match find_object(&svc_path, object) {
+                    Ok(hash) if matches!(read_object(&svc_path, &hash), Ok((ObjectType::ObjectTree, _))) => hash,
+                    _ => {
+                        eprintln!("error: '{}' is neither a version nor a tree.", object);
+                        process::exit(1)
+                    }
+                },
+            };
+            let path = match path {
+                Some(path) => path.trim_start_matches("./").trim_end_matches('/'),
+                None => "",
+            };
+            let result = if path.is_empty() {
+                print_tree(&svc_path, &tree_hash, "", recursive)
+            } else {
+                match find_entry_in_tree(&svc_path, &tree_hash, path) {
+                    Ok(Some(entry)) if entry.object_type == ObjectType::ObjectTree => {
+                        print_tree(&svc_path, &entry.hash, &format!("{}/", path), recursive)
+                    }
+                    Ok(Some(entry)) => {
+                        print_tree_entry(&entry, path);
+                        Ok(())
+                    }
+                    Ok(None) => {
+                        eprintln!("error: '{}' is not in this tree.", path);
+                        process::exit(1)
+                    }
+                    Err(err) => Err(err),
+                }
+            };
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn print_tree(svc_path: &Path, tree_hash: &str, prefix: &str, recursive: bool) -> io::Result<()> {
+    for entry in TreeEntry::read_tree(object_path(svc_path, tree_hash))? {
+        let path = format!("{}{}", prefix, entry.name);
+        print_tree_entry(&entry, &path);
+        if recursive && entry.object_type == ObjectType::ObjectTree {
+            print_tree(svc_path, &entry.hash, &format!("{}/", path), recursive)?;
+        }
+    }
+    Ok(())
+}
+
+fn print_tree_entry(entry: &TreeEntry, path: &str) {
+    println!("{:o} {} {} {:>8}\t{}", entry.mode, entry.object_type, entry.hash, entry.size, path);
+}
+
+pub fn hash_object(file: &str, write: bool) {
+    let file_path = PathBuf::from(file);
+    // hashed the same way a commit does, a link by its target
+    let object = match fs::symlink_metadata(&file_path) {
+        Ok(meta) if meta.file_type().is_symlink() => {
+            let target = read_link_target(file_path);
+            Ok((get_str_hash(&target), target.into_bytes()))
+        }
+        Ok(meta) if meta.is_file() => fs::read(&file_path).map(|data| (get_file_hash(file_path), data)),
+        Ok(_) => Err(io::Error::other(format!("'{}' is not a file", file))),
+        Err(err) => Err(err),
+    };
+    let (hash, data) = match object {
+        Ok(object) => object,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1)
+        }
+    };
+    if write {
+        match check_svc_repo() {
+            Ok(svc_path) => {
+                if let Err(err) = TreeEntry::write_blob_data(&data, svc_path, &hash) {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+            Err(err) => {
+                eprintln!("{}", err);
                 process::exit(1)
             }
-            
+        }
+    }
+    println!("{}", hash);
+}
+
+pub fn check_ignore(paths: &[String]) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let root_path = svc_path.parent().unwrap().to_path_buf();
+            let exclude = Commit::read_ignore(svc_path.clone());
+            for path in paths {
+                let full_path = match workspace_path(&root_path, path) {
+                    Some(relative_path) if relative_path.is_empty() => root_path.clone(),
+                    Some(relative_path) => root_path.join(relative_path),
+                    None => {
+                        eprintln!("error: '{}' is outside repository", path);
+                        process::exit(1);
+                    }
+                };
+                let is_dir = path.ends_with('/') || full_path.is_dir();
+                if full_path == svc_path {
+                    println!("{}: always ignored", path);
+                    continue;
+                }
+                match exclude.explain(&root_path, &full_path, is_dir) {
+                    Some(rule) => {
+                        let source = rule.source.strip_prefix(&root_path).unwrap_or(&rule.source);
+                        println!(
+                            "{}: {} by {}:{}:{}",
+                            path,
+                            if rule.negated { "included" } else { "ignored" },
+                            source.to_str().unwrap(),
+                            rule.line,
+                            rule.pattern
+                        );
+                    }
+                    None => println!("{}: no rule matched", path),
+                }
+            }
         }
         Err(err) => {
             eprintln!("{}", err);
@@ -219,3 +975,47 @@ pub fn checkout(version: &String) {
         }
     }
 }
+
+// a path given on the command line, relative to the workspace root with '/'
+// separators, None when it is outside the workspace
+fn workspace_path(root_path: &Path, path: &str) -> Option<String> {
+    let full_path = env::current_dir().unwrap().join(path);
+    let relative_path = full_path.strip_prefix(root_path).ok()?;
+    let mut parts = Vec::new();
+    for component in relative_path.components() {
+        match component {
+            Component::ParentDir => {
+                parts.pop()?;
+            }
+            Component::Normal(name) => parts.push(name.to_str()?),
+            _ => (),
+        }
+    }
+    Some(parts.join("/"))
+}
+
+// compare the local log with the default remote's log as last seen
+fn get_remote_state(svc_path: PathBuf) -> Option<String> {
+    let name = RepoMeta::read_repo_meta(svc_path.clone()).default_remote;
+    let remote_log = read_remote_log(svc_path.clone(), &name)?;
+    let remote_commits = Commit::parse_log(&remote_log);
+    let local_commits = Commit::read_from_log(svc_path);
+    let ahead = local_commits
+        .iter()
+        .filter(|commit| !remote_commits.iter().any(|remote| remote.hash == commit.hash))
+        .count();
+    let behind = remote_commits
+        .iter()
+        .filter(|remote| !local_commits.iter().any(|commit| commit.hash == remote.hash))
+        .count();
+    let state = match (ahead, behind) {
+        (0, 0) => format!("up to date with '{}'.", name),
+        (ahead, 0) => format!("ahead of '{}' by {} commit(s).", name, ahead),
+        (0, behind) => format!("behind '{}' by {} commit(s).", name, behind),
+        (ahead, behind) => format!(
+            "diverged from '{}': ahead by {}, behind by {} commit(s).",
+            name, ahead, behind
+        ),
+    };
+    Some(state)
+}
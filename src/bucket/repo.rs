@@ -1,25 +1,38 @@
 use chrono::Local;
 use std::{
     fmt::Display,
-    fs::File,
-    io::{BufRead, BufReader, Error, Write},
+    fs::{self, File},
+    io::{Error, Read, Write},
     path::PathBuf,
 };
+
+pub const DEFAULT_REMOTE: &str = "origin";
+
 pub struct RepoMeta {
     pub repo_name: String,
     pub repo_intro: String,
-    pub remote_url: String,
     pub created_at: String,
     pub updated_at: String,
+    pub default_remote: String,
+    pub remotes: Vec<Remote>,
+}
+
+pub struct Remote {
+    pub name: String,
+    pub url: String,
 }
 
 impl Display for RepoMeta {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(
             f,
-            "repo_name = {}\nrepo_intro = {}\nremote_url = {}\ncreated_at = {}\nupdated_at = {}",
-            self.repo_name, self.repo_intro, self.remote_url, self.created_at, self.updated_at
-        )
+            "repo_name = {}\nrepo_intro = {}\ncreated_at = {}\nupdated_at = {}\ndefault_remote = {}",
+            self.repo_name, self.repo_intro, self.created_at, self.updated_at, self.default_remote
+        )?;
+        for remote in &self.remotes {
+            write!(f, "\nremote.{} = {}", remote.name, remote.url)?;
+        }
+        Ok(())
     }
 }
 
@@ -27,30 +40,44 @@ impl RepoMeta {
     pub fn new(repo_name: String, repo_intro: String, remote_url: String) -> RepoMeta {
         let created_at = Local::now().format("%Y-%m-%d %H:%M").to_string();
         let updated_at = created_at.clone();
+        let mut remotes = Vec::new();
+        if !remote_url.trim().is_empty() {
+            remotes.push(Remote {
+                name: DEFAULT_REMOTE.to_string(),
+                url: remote_url.trim().to_string(),
+            });
+        }
 
         // println!("{:?}", created_at);
         RepoMeta {
             repo_name,
             repo_intro,
-            remote_url,
             created_at,
             updated_at,
+            default_remote: DEFAULT_REMOTE.to_string(),
+            remotes,
         }
     }
 
     pub fn read_repo_meta(svc_path: PathBuf) -> RepoMeta {
         // println!("{:?}", svc_path.join("repo"));
-        let file =
+        let mut file =
             File::open(svc_path.join("repo")).expect("can not read .svc/repo, maybe it lost?");
-        let file = BufReader::new(file);
+        let mut data = String::new();
+        file.read_to_string(&mut data).unwrap();
+        RepoMeta::parse_repo_meta(&data)
+    }
+
+    pub fn parse_repo_meta(data: &str) -> RepoMeta {
         let mut repo_name = String::from("");
         let mut repo_intro = String::from("");
         let mut remote_url = String::from("");
         let mut created_at = String::from("");
         let mut updated_at = String::from("");
-        for line in file.lines() {
-            let line = line.unwrap();
-            let line: Vec<&str> = line.split('=').collect();
+        let mut default_remote = String::from("");
+        let mut remotes: Vec<Remote> = Vec::new();
+        for line in data.lines() {
+            let line: Vec<&str> = line.splitn(2, '=').collect();
             if line.len() != 2 {
                 continue;
             }
@@ -72,52 +99,138 @@ impl RepoMeta {
                 "updated_at" => {
                     updated_at = v;
                 }
-                _ => (),
+                "default_remote" => {
+                    default_remote = v;
+                }
+                _ => {
+                    if let Some(name) = k.strip_prefix("remote.") {
+                        remotes.push(Remote {
+                            name: name.to_string(),
+                            url: v,
+                        });
+                    }
+                }
             }
         }
+        // repos made before named remotes had a single remote_url
+        if !remote_url.is_empty() && !remotes.iter().any(|remote| remote.name == DEFAULT_REMOTE) {
+            remotes.insert(0, Remote {
+                name: DEFAULT_REMOTE.to_string(),
+                url: remote_url,
+            });
+        }
+        if default_remote.is_empty() {
+            default_remote = DEFAULT_REMOTE.to_string();
+        }
         RepoMeta {
             repo_name,
             repo_intro,
-            remote_url,
             created_at,
             updated_at,
+            default_remote,
+            remotes,
         }
     }
 
+    // fields set to "\n" keep their old value, remotes are merged by name
     pub fn update_repo_meta(new_meta: RepoMeta, svc_path: PathBuf) -> Result<(), Error> {
-        let old_meta = RepoMeta::read_repo_meta(svc_path.clone());
-        let mut repo_name = old_meta.repo_name;
-        let mut repo_intro = old_meta.repo_intro;
-        let mut remote_url = old_meta.remote_url;
-        let mut created_at = old_meta.created_at;
-        let mut updated_at = old_meta.updated_at;
+        let mut meta = RepoMeta::read_repo_meta(svc_path.clone());
         if new_meta.repo_name != "\n" {
-            repo_name = new_meta.repo_name;
+            meta.repo_name = new_meta.repo_name;
         }
         if new_meta.repo_intro != "\n" {
-            repo_intro = new_meta.repo_intro;
-        }
-        if new_meta.remote_url != "\n" {
-            remote_url = new_meta.remote_url;
+            meta.repo_intro = new_meta.repo_intro;
         }
         if new_meta.created_at != "\n" {
-            created_at = new_meta.created_at;
+            meta.created_at = new_meta.created_at;
         }
         if new_meta.updated_at != "\n" {
-            updated_at = new_meta.updated_at;
+            meta.updated_at = new_meta.updated_at;
+        }
+        if new_meta.default_remote != "\n" {
+            meta.default_remote = new_meta.default_remote;
+        }
+        for remote in new_meta.remotes {
+            meta.set_remote(remote.name, remote.url);
         }
+        meta.write_repo_meta(svc_path)
+    }
+
+    // record that the repo changed just now
+    pub fn touch_updated_at(svc_path: PathBuf) -> Result<(), Error> {
+        RepoMeta::update_repo_meta(RepoMeta {
+            repo_name: "\n".to_string(),
+            repo_intro: "\n".to_string(),
+            created_at: "\n".to_string(),
+            updated_at: Local::now().format("%Y-%m-%d %H:%M").to_string(),
+            default_remote: "\n".to_string(),
+            remotes: Vec::new(),
+        }, svc_path)
+    }
+
+    pub fn write_repo_meta(&self, svc_path: PathBuf) -> Result<(), Error> {
         // println!("{:?}", svc_path.join("repo"));
         let mut file =
             File::create(svc_path.join("repo")).expect("can not open .svc/repo, maybe it lost?");
-        
-        file.write_fmt(format_args!("repo_name = {}\n", repo_name.trim()))?;
-        file.write_fmt(format_args!("repo_intro = {}\n", repo_intro.trim()))?;
-        file.write_fmt(format_args!("remote_url = {}\n", remote_url.trim()))?;
-        file.write_fmt(format_args!("created_at = {}\n", created_at.trim()))?;
-        file.write_fmt(format_args!("updated_at = {}\n", updated_at.trim()))?;
+
+        file.write_fmt(format_args!("repo_name = {}\n", self.repo_name.trim()))?;
+        file.write_fmt(format_args!("repo_intro = {}\n", self.repo_intro.trim()))?;
+        file.write_fmt(format_args!("created_at = {}\n", self.created_at.trim()))?;
+        file.write_fmt(format_args!("updated_at = {}\n", self.updated_at.trim()))?;
+        file.write_fmt(format_args!("default_remote = {}\n", self.default_remote.trim()))?;
+        for remote in &self.remotes {
+            file.write_fmt(format_args!("remote.{} = {}\n", remote.name, remote.url.trim()))?;
+        }
 
         Ok(())
     }
+
+    /// The metadata a remote may read, leaving out where this repo syncs to.
+    pub fn public_meta(&self) -> String {
+        format!(
+            "repo_name = {}\nrepo_intro = {}\ncreated_at = {}\nupdated_at = {}\n",
+            self.repo_name.trim(),
+            self.repo_intro.trim(),
+            self.created_at.trim(),
+            self.updated_at.trim()
+        )
+    }
+
+    pub fn get_remote(&self, name: &str) -> Option<&Remote> {
+        self.remotes.iter().find(|remote| remote.name == name)
+    }
+
+    pub fn set_remote(&mut self, name: String, url: String) {
+        match self.remotes.iter_mut().find(|remote| remote.name == name) {
+            Some(remote) => remote.url = url,
+            None => self.remotes.push(Remote { name, url }),
+        }
+    }
+}
+
+// the log of a remote as we saw it on the last push, pull or fetch
+pub fn read_remote_log(svc_path: PathBuf, name: &str) -> Option<String> {
+    let mut log = String::new();
+    File::open(svc_path.join("remotes").join(name).join("log"))
+        .and_then(|mut file| file.read_to_string(&mut log))
+        .ok()?;
+    Some(log)
+}
+
+pub fn write_remote_log(svc_path: PathBuf, name: &str, log: &str) -> Result<(), Error> {
+    let remote_dir = svc_path.join("remotes").join(name);
+    fs::create_dir_all(&remote_dir)?;
+    File::create(remote_dir.join("log"))?.write_all(log.as_bytes())
+}
+
+pub fn create_svc_repo(root_path: PathBuf) -> Result<PathBuf, Error> {
+    let svc_path = root_path.join(".svc");
+    fs::create_dir(&svc_path)?;
+    fs::create_dir(svc_path.join("objects"))?;
+    for name in ["repo", "head", "log", "latest"] {
+        File::create(svc_path.join(name))?;
+    }
+    Ok(svc_path)
 }
 
 pub fn check_svc_repo() -> Result<PathBuf, &'static str> {
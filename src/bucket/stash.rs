@@ -0,0 +1,152 @@
+use crate::bucket::log::{get_tree_of_commit, Commit};
+use crate::bucket::merge::{check_no_pending, merge_trees};
+use crate::bucket::tree::{flatten_tree, read_link_target, ObjectType, Tree, TreeEntry};
+use crate::util::{get_file_hash, get_str_hash};
+use std::{
+    collections::BTreeMap,
+    fs,
+    io::Error,
+    path::PathBuf,
+};
+
+// saved workspaces as log lines, their parent is HEAD at the time, newest last
+const STASH: &str = "stash";
+
+pub fn read_stash(svc_path: PathBuf) -> Vec<Commit> {
+    match fs::read_to_string(svc_path.join(STASH)) {
+        Ok(data) => Commit::parse_log(&data),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn write_stash(svc_path: PathBuf, entries: &[Commit]) -> Result<(), Error> {
+    if entries.is_empty() {
+        return match fs::remove_file(svc_path.join(STASH)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err),
+            _ => Ok(()),
+        };
+    }
+    let data: String = entries.iter().map(|entry| entry.to_log_line()).collect();
+    fs::write(svc_path.join(STASH), data)
+}
+
+/// Look up an entry by `stash@{n}` or `n`, the newest being 0, and return it
+/// with its number.
+pub fn get_stash(svc_path: PathBuf, name: &Option<String>) -> Result<(usize, Commit), Error> {
+    let entries = read_stash(svc_path);
+    let index = find_stash(&entries, name)?;
+    Ok((index, entries[entries.len() - 1 - index].clone()))
+}
+
+fn find_stash(entries: &[Commit], name: &Option<String>) -> Result<usize, Error> {
+    let index = match name {
+        Some(name) => {
+            let number = name.strip_prefix("stash@{").and_then(|rest| rest.strip_suffix('}')).unwrap_or(name);
+            number.parse().map_err(|_| Error::other(format!("'{}' is not a stash entry", name)))?
+        }
+        None => 0,
+    };
+    if entries.is_empty() {
+        return Err(Error::other("no stash entries"));
+    }
+    if index >= entries.len() {
+        return Err(Error::other(format!("stash@{{{}}} not found", index)));
+    }
+    Ok(index)
+}
+
+// save the workspace like a commit would, leaving out untracked files unless asked
+fn save_workspace(svc_path: PathBuf, head_tree: &str, include_untracked: bool) -> Result<String, Error> {
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    let tree_hash = Commit::save_workspace(svc_path.clone());
+    if include_untracked {
+        return Ok(tree_hash);
+    }
+    let tracked = flatten_tree(svc_path.clone(), head_tree)?;
+    let mut entries: BTreeMap<String, TreeEntry> = flatten_tree(svc_path.clone(), &tree_hash)?
+        .into_iter()
+        .filter(|(path, _)| tracked.contains_key(path))
+        .collect();
+    // a tracked empty directory that only got untracked files is still there
+    for (path, entry) in tracked {
+        if entry.object_type == ObjectType::ObjectTree && root_path.join(&path).is_dir() {
+            entries.entry(path).or_insert(entry);
+        }
+    }
+    Ok(Tree::from_entries(svc_path, &entries)?.hash)
+}
+
+/// Save the local changes as a new stash entry and reset the workspace to HEAD.
+pub fn push_stash(svc_path: PathBuf, message: Option<String>, include_untracked: bool) -> Result<(), Error> {
+    check_no_pending(svc_path.clone())?;
+    let head = Commit::find_commit(svc_path.clone(), "HEAD").map_err(|_| Error::other("no commit yet"))?;
+    let tree_hash = save_workspace(svc_path.clone(), &head.tree_hash, include_untracked)?;
+    if tree_hash == head.tree_hash {
+        println!("no local changes to save.");
+        return Ok(());
+    }
+    let message = message.unwrap_or_else(|| format!("WIP on {}: {}", &head.hash[..8], head.message.lines().next().unwrap_or("")));
+    let entry = Commit::from_tree(message, head.hash.clone(), tree_hash.clone());
+    let mut entries = read_stash(svc_path.clone());
+    entries.push(entry.clone());
+    write_stash(svc_path.clone(), &entries)?;
+    // back to HEAD, dropping the files that were just saved
+    Commit::restore_path(svc_path, "", TreeEntry::root(&head.tree_hash), Some(&tree_hash))?;
+    println!("save workspace as stash@{{0}}: {}", entry.message);
+    Ok(())
+}
+
+/// Apply a stash entry over the workspace with a three-way merge against the
+/// commit it was made on, and drop it unless there were conflicts.
+pub fn pop_stash(svc_path: PathBuf, name: &Option<String>) -> Result<(), Error> {
+    check_no_pending(svc_path.clone())?;
+    let root_path = svc_path.parent().unwrap().to_path_buf();
+    let mut entries = read_stash(svc_path.clone());
+    let index = find_stash(&entries, name)?;
+    let entry = entries[entries.len() - 1 - index].clone();
+    let head = Commit::find_commit(svc_path.clone(), "HEAD").map_err(|_| Error::other("no commit yet"))?;
+
+    // the workspace itself is our side, so local changes are merged, not lost
+    let base_tree = get_tree_of_commit(svc_path.clone(), entry.parent_hash.clone()).ok();
+    let ours_tree = save_workspace(svc_path.clone(), &head.tree_hash, false)?;
+    let label = format!("stash@{{{}}}", index);
+    let result = merge_trees(svc_path.clone(), base_tree.as_deref(), &ours_tree, &entry.tree_hash, &label)?;
+    let ours = flatten_tree(svc_path.clone(), &ours_tree)?;
+    for (path, new_entry) in flatten_tree(svc_path.clone(), &result.tree_hash)? {
+        if new_entry.object_type == ObjectType::ObjectTree || ours.contains_key(&path) {
+            continue;
+        }
+        let file_path = root_path.join(&path);
+        let same = match fs::symlink_metadata(&file_path) {
+            Ok(meta) if meta.file_type().is_symlink() => get_str_hash(&read_link_target(file_path)) == new_entry.hash,
+            Ok(meta) if meta.is_file() => get_file_hash(file_path) == new_entry.hash,
+            Ok(_) => false,
+            Err(_) => true,
+        };
+        if !same {
+            return Err(Error::other(format!("untracked '{}' would be overwritten by {}", path, label)));
+        }
+    }
+    Commit::restore_path(svc_path.clone(), "", TreeEntry::root(&result.tree_hash), Some(&ours_tree))?;
+
+    if !result.conflicts.is_empty() {
+        for conflict in &result.conflicts {
+            println!("conflict ({}): {}", conflict.kind, conflict.path);
+        }
+        println!("{} is kept, fix the conflicts and drop it with \"svc stash drop\".", label);
+        return Ok(());
+    }
+    entries.remove(entries.len() - 1 - index);
+    write_stash(svc_path, &entries)?;
+    println!("apply and drop {} ({}).", label, entry.hash);
+    Ok(())
+}
+
+pub fn drop_stash(svc_path: PathBuf, name: &Option<String>) -> Result<(), Error> {
+    let mut entries = read_stash(svc_path.clone());
+    let index = find_stash(&entries, name)?;
+    let entry = entries.remove(entries.len() - 1 - index);
+    write_stash(svc_path, &entries)?;
+    println!("drop stash@{{{}}} ({}).", index, entry.hash);
+    Ok(())
+}
@@ -1,23 +1,38 @@
-use crate::util::{compress_data, decompress_data, get_file_hash, get_str_hash};
+use crate::bucket::ignore::Ignore;
+use crate::util::{compress_data, get_data_hash, get_file_hash, get_str_hash, try_decompress_data};
 use std::{
-    collections::HashMap,
+    collections::BTreeMap,
     fmt::Display,
     fs::{self, File},
-    io::{Error, Read, Write, BufReader, BufRead},
-    path::PathBuf,
+    io::{Error, Read, Write},
+    path::{Path, PathBuf},
     process,
 };
 
+#[derive(Clone)]
 pub struct TreeEntry {
     pub hash: String,
     pub object_type: ObjectType,
     pub size: u64,
+    pub mode: u32,
     pub name: String,
 }
 
+// trees written with this version in their header line carry mode bits
+const TREE_VERSION: &str = "3";
+// the same, from before tree hashes got their own prefix
+const TREE_VERSION_UNPREFIXED: &str = "2";
+
+pub const MODE_FILE: u32 = 0o644;
+pub const MODE_DIR: u32 = 0o755;
+pub const MODE_LINK: u32 = 0o777;
+
+#[allow(clippy::enum_variant_names)]
+#[derive(Clone, Copy, PartialEq)]
 pub enum ObjectType {
     ObjectBlob,
     ObjectTree,
+    ObjectLink,
 }
 
 impl Display for ObjectType {
@@ -25,6 +40,7 @@ impl Display for ObjectType {
         match self {
             ObjectType::ObjectBlob => write!(f, "blob"),
             ObjectType::ObjectTree => write!(f, "tree"),
+            ObjectType::ObjectLink => write!(f, "link"),
         }
     }
 }
@@ -34,10 +50,11 @@ impl TreeEntry {
         entry_path: PathBuf,
         object_type: ObjectType,
         svc_path: PathBuf,
-        exclude: &HashMap<PathBuf, bool>,
+        exclude: &Ignore,
     ) -> TreeEntry {
         let size;
         let hash;
+        let mode;
         let name = entry_path
             .file_name()
             .unwrap()
@@ -47,43 +64,83 @@ impl TreeEntry {
         match object_type {
             ObjectType::ObjectBlob => {
                 size = fs::metadata(entry_path.clone()).unwrap().len();
+                mode = get_file_mode(entry_path.clone());
                 hash = get_file_hash(entry_path);
             }
             ObjectType::ObjectTree => {
                 let tree = Tree::new(entry_path, svc_path, exclude);
                 size = tree.size;
+                mode = MODE_DIR;
                 hash = tree.hash;
             }
+            ObjectType::ObjectLink => {
+                // a link is stored as a blob holding its target
+                let target = read_link_target(entry_path);
+                size = target.len() as u64;
+                mode = MODE_LINK;
+                hash = get_str_hash(&target);
+            }
         }
         TreeEntry {
             object_type,
             hash,
             name,
             size,
+            mode,
+        }
+    }
+
+    /// Save a file, link or directory of the workspace and return its entry.
+    pub fn save(entry_path: PathBuf, file_type: fs::FileType, svc_path: PathBuf, exclude: &Ignore) -> Result<TreeEntry, Error> {
+        if file_type.is_symlink() {
+            let tree_entry = TreeEntry::new(entry_path.clone(), ObjectType::ObjectLink, svc_path.clone(), exclude);
+            let target = read_link_target(entry_path);
+            TreeEntry::save_blob_data(target.as_bytes(), svc_path, &tree_entry.hash)?;
+            Ok(tree_entry)
+        } else if file_type.is_dir() {
+            Ok(TreeEntry::new(entry_path, ObjectType::ObjectTree, svc_path, exclude))
+        } else {
+            let tree_entry = TreeEntry::new(entry_path.clone(), ObjectType::ObjectBlob, svc_path.clone(), exclude);
+            TreeEntry::save_blob(entry_path, svc_path, &tree_entry.hash)?;
+            Ok(tree_entry)
+        }
+    }
+
+    // the entry a whole tree would have in a parent
+    pub fn root(tree_hash: &str) -> TreeEntry {
+        TreeEntry {
+            hash: tree_hash.to_string(),
+            object_type: ObjectType::ObjectTree,
+            size: 0,
+            mode: MODE_DIR,
+            name: String::new(),
         }
     }
 
     fn save_blob(entry_path: PathBuf, svc_path: PathBuf, hash: &str) -> Result<(), Error> {
+        let mut data = Vec::new();
+        File::open(entry_path)?.read_to_end(&mut data)?;
+        TreeEntry::save_blob_data(&data, svc_path, hash)
+    }
+
+    fn save_blob_data(data: &[u8], svc_path: PathBuf, hash: &str) -> Result<(), Error> {
+        println!("blob {:?}", hash);
+        TreeEntry::write_blob_data(data, svc_path, hash)?;
+        println!("blob {:?}", hash);
+        Ok(())
+    }
+
+    // the same without reporting it
+    pub fn write_blob_data(data: &[u8], svc_path: PathBuf, hash: &str) -> Result<(), Error> {
         let dir = &hash[0..2];
         let filename = &hash[2..];
-        println!("blob {:?}", hash);
         match fs::create_dir(svc_path.join("objects").join(dir)) {
             Ok(_) => (),
             Err(ref e) if e.kind() == std::io::ErrorKind::AlreadyExists => (),
             Err(e) => return Err(e),
         };
         let mut file_write = File::create(svc_path.join("objects").join(dir).join(filename))?;
-        let mut file_read = File::open(entry_path)?;
-        let mut buf = [0; 1024];
-
-        while let Ok(bytes_read) = file_read.read(&mut buf) {
-            if bytes_read == 0 {
-                break;
-            }
-            let buf = compress_data(&buf[0..bytes_read]);
-            file_write.write(&buf)?;
-        }
-        println!("blob {:?}", hash);
+        file_write.write_all(&compress_data(data))?;
         Ok(())
     }
 
@@ -97,56 +154,94 @@ impl TreeEntry {
             Err(e) => return Err(e),
         };
         let mut file_write = File::create(svc_path.join("objects").join(dir).join(filename))?;
-        file_write.write_fmt(format_args!("{} {}\n", tree.hash, tree.size))?;
+        file_write.write_fmt(format_args!("{} {} {}\n", tree.hash, tree.size, TREE_VERSION))?;
         for entry in &tree.records {
             file_write.write_fmt(format_args!(
-                "{} {} {} {}\n",
-                entry.hash, entry.object_type, entry.size, entry.name
+                "{} {} {} {:o} {}\n",
+                entry.hash, entry.object_type, entry.size, entry.mode, entry.name
             ))?;
         }
         println!("tree {:?}", tree.hash);
         Ok(())
     }
 
-    pub fn read_tree(tree_path: PathBuf) -> Vec<TreeEntry> {
-        let file = File::open(tree_path).unwrap();
-        let reader = BufReader::new(file);
+    pub fn read_tree(tree_path: PathBuf) -> Result<Vec<TreeEntry>, Error> {
+        let mut data = String::new();
+        File::open(tree_path)?.read_to_string(&mut data)?;
+        TreeEntry::parse_tree(&data)
+    }
+
+    pub fn parse_tree(data: &str) -> Result<Vec<TreeEntry>, Error> {
+        let corrupted = || Error::other("tree is corrupted");
         let mut tree_entries = Vec::new();
-        for line in reader.lines() {
-            let line = line.unwrap();
+        let mut with_mode = false;
+        for (i, line) in data.lines().enumerate() {
             let line: Vec<&str> = line.split(" ").collect();
-            if line.len() < 4 {
+            if i == 0 {
+                with_mode = line.len() == 3 && (line[2] == TREE_VERSION || line[2] == TREE_VERSION_UNPREFIXED);
                 continue;
             }
+            if line.len() < 4 || (with_mode && line.len() < 5) {
+                return Err(corrupted());
+            }
             let object_type = match line[1] {
                 "blob" => ObjectType::ObjectBlob,
                 "tree" => ObjectType::ObjectTree,
-                _ => ObjectType::ObjectBlob
+                "link" => ObjectType::ObjectLink,
+                _ => return Err(corrupted()),
+            };
+            let (mode, name) = if with_mode {
+                let mode = u32::from_str_radix(line[3], 8).map_err(|_| corrupted())?;
+                (mode, line[4..].join(" "))
+            } else {
+                let mode = match object_type {
+                    ObjectType::ObjectBlob => MODE_FILE,
+                    ObjectType::ObjectTree => MODE_DIR,
+                    ObjectType::ObjectLink => MODE_LINK,
+                };
+                (mode, line[3..].join(" "))
             };
             let tree_entry = TreeEntry {
                 hash: line[0].to_string(),
                 object_type,
-                size: line[2].parse::<u64>().unwrap(),
-                name: line[3..].join(" ")
+                size: line[2].parse::<u64>().map_err(|_| corrupted())?,
+                mode,
+                name,
             };
             tree_entries.push(tree_entry);
         }
-        tree_entries
+        Ok(tree_entries)
+    }
+
+    pub fn read_blob(blob_path: PathBuf) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        File::open(blob_path)?.read_to_end(&mut data)?;
+        try_decompress_data(&data)
     }
 
-    pub fn restore_blob(file_path: PathBuf, blob_path: PathBuf) -> Result<(), Error> {
-        let mut file_read = File::open(blob_path)?;
-        let mut file_write = File::create(file_path)?;
-        let mut buf = [0; 1024];
+    pub fn restore_blob(file_path: PathBuf, blob_path: PathBuf, mode: u32) -> Result<(), Error> {
+        let data = TreeEntry::read_blob(blob_path)?;
+        // never write through a link, replace it instead
+        if let Ok(meta) = fs::symlink_metadata(&file_path) {
+            if meta.file_type().is_symlink() {
+                fs::remove_file(&file_path)?;
+            }
+        }
+        let mut file_write = File::create(file_path.clone())?;
+        file_write.write_all(&data)?;
+        set_file_mode(file_path, mode)
+    }
 
-        while let Ok(bytes_read) = file_read.read(&mut buf) {
-            if bytes_read == 0 {
-                break;
+    pub fn restore_link(link_path: PathBuf, blob_path: PathBuf) -> Result<(), Error> {
+        let target = String::from_utf8_lossy(&TreeEntry::read_blob(blob_path)?).to_string();
+        if let Ok(meta) = fs::symlink_metadata(&link_path) {
+            if meta.is_dir() {
+                fs::remove_dir(&link_path)?;
+            } else {
+                fs::remove_file(&link_path)?;
             }
-            let buf = decompress_data(&buf[0..bytes_read]);
-            file_write.write(&buf)?;
         }
-        Ok(())
+        create_symlink(PathBuf::from(target), link_path)
     }
 }
 
@@ -157,55 +252,304 @@ pub struct Tree {
 }
 
 impl Tree {
-    pub fn new(dir: PathBuf, svc_path: PathBuf, exclude: &HashMap<PathBuf, bool>) -> Tree {
+    pub fn new(dir: PathBuf, svc_path: PathBuf, exclude: &Ignore) -> Tree {
         let mut records: Vec<TreeEntry> = Vec::new();
-        let mut str = String::from("");
         let mut size = 0;
-        let mut tree_entry;
 
         for entry in fs::read_dir(dir).unwrap() {
             let entry = entry.unwrap();
-            if (entry.file_name().to_str().unwrap().starts_with('.') && entry.file_name() != ".svcignore")
-                || exclude.contains_key(&entry.path()) {
+            let file_type = entry.file_type().unwrap();
+            if exclude.is_ignored(&entry.path(), file_type.is_dir()) {
                 continue;
             }
             println!("{:?}", entry.file_name());
-            let metadata = entry.metadata().unwrap();
-            if metadata.is_dir() {
-                tree_entry = TreeEntry::new(
-                    entry.path(),
-                    ObjectType::ObjectTree,
-                    svc_path.clone(),
-                    exclude,
-                );
-            } else {
-                tree_entry = TreeEntry::new(
-                    entry.path(),
-                    ObjectType::ObjectBlob,
-                    svc_path.clone(),
-                    exclude,
-                );
-                if let Err(err) =
-                    TreeEntry::save_blob(entry.path(), svc_path.clone(), &tree_entry.hash)
-                {
+            let tree_entry = match TreeEntry::save(entry.path(), file_type, svc_path.clone(), exclude) {
+                Ok(tree_entry) => tree_entry,
+                Err(err) => {
                     eprintln!("error: {}", err);
                     process::exit(1);
                 }
-            }
-            str += &tree_entry.hash;
+            };
             size += tree_entry.size;
             records.push(tree_entry);
         }
-        let hash = get_str_hash(&str);
+        match Tree::from_records(records, size, svc_path) {
+            Ok(tree) => tree,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+    }
+
+    /// Save the trees holding entries keyed by their path relative to the
+    /// root, as returned by `flatten_tree`. Tree entries only mark directories.
+    pub fn from_entries(svc_path: PathBuf, entries: &BTreeMap<String, TreeEntry>) -> Result<Tree, Error> {
+        let mut records: Vec<TreeEntry> = Vec::new();
+        let mut dirs: BTreeMap<String, BTreeMap<String, TreeEntry>> = BTreeMap::new();
+        for (path, entry) in entries {
+            match path.split_once('/') {
+                Some((dir, rest)) => {
+                    dirs.entry(dir.to_string()).or_default().insert(rest.to_string(), entry.clone());
+                }
+                None if entry.object_type == ObjectType::ObjectTree => {
+                    dirs.entry(path.clone()).or_default();
+                }
+                None => records.push(TreeEntry {
+                    name: path.clone(),
+                    ..entry.clone()
+                }),
+            }
+        }
+        for (name, dir_entries) in dirs {
+            if records.iter().any(|record| record.name == name) {
+                return Err(Error::other(format!("'{}' is both a file and a directory", name)));
+            }
+            let tree = Tree::from_entries(svc_path.clone(), &dir_entries)?;
+            records.push(TreeEntry {
+                hash: tree.hash,
+                object_type: ObjectType::ObjectTree,
+                size: tree.size,
+                mode: MODE_DIR,
+                name,
+            });
+        }
+        let size = records.iter().map(|record| record.size).sum();
+        Tree::from_records(records, size, svc_path)
+    }
+
+    pub fn from_records(mut records: Vec<TreeEntry>, size: u64, svc_path: PathBuf) -> Result<Tree, Error> {
+        records.sort_by(|a, b| a.name.cmp(&b.name));
+        let hash = hash_records(&records, TREE_VERSION);
         let tree = Tree {
             hash,
             size,
             records,
         };
-        if let Err(err) = TreeEntry::save_tree(&tree, svc_path) {
-            eprintln!("error: {}", err);
-            process::exit(1);
+        TreeEntry::save_tree(&tree, svc_path)?;
+        Ok(tree)
+    }
+}
+
+/// Every file and link of a tree keyed by its path relative to the tree,
+/// directories are only listed when they are empty.
+pub fn flatten_tree(svc_path: PathBuf, tree_hash: &str) -> Result<BTreeMap<String, TreeEntry>, Error> {
+    let mut entries = BTreeMap::new();
+    flatten_tree_into(&svc_path, tree_hash, "", &mut entries)?;
+    Ok(entries)
+}
+
+fn flatten_tree_into(
+    svc_path: &PathBuf,
+    tree_hash: &str,
+    prefix: &str,
+    entries: &mut BTreeMap<String, TreeEntry>,
+) -> Result<(), Error> {
+    let tree_path = svc_path.join("objects").join(&tree_hash[0..2]).join(&tree_hash[2..]);
+    for entry in TreeEntry::read_tree(tree_path)? {
+        let path = format!("{}{}", prefix, entry.name);
+        if entry.object_type == ObjectType::ObjectTree {
+            let count = entries.len();
+            flatten_tree_into(svc_path, &entry.hash, &format!("{}/", path), entries)?;
+            if entries.len() > count {
+                continue;
+            }
+        }
+        entries.insert(path, entry);
+    }
+    Ok(())
+}
+
+/// Look up the entry at a '/' separated path inside a tree.
+pub fn find_entry_in_tree(svc_path: &Path, tree_hash: &str, path: &str) -> Result<Option<TreeEntry>, Error> {
+    let (name, rest) = match path.split_once('/') {
+        Some((name, rest)) => (name, rest),
+        None => (path, ""),
+    };
+    let entry = match TreeEntry::read_tree(object_path(svc_path, tree_hash))?
+        .into_iter()
+        .find(|entry| entry.name == name)
+    {
+        Some(entry) => entry,
+        None => return Ok(None),
+    };
+    if rest.is_empty() {
+        return Ok(Some(entry));
+    }
+    match entry.object_type {
+        ObjectType::ObjectTree => find_entry_in_tree(svc_path, &entry.hash, rest),
+        _ => Ok(None),
+    }
+}
+
+/// Check that tree data read from elsewhere is the tree named `hash`, trees
+/// from before mode bits are hashed the old way, from their entry hashes.
+pub fn check_tree_hash(hash: &str, data: &str) -> bool {
+    let header: Vec<&str> = data.lines().next().unwrap_or("").split(' ').collect();
+    if header[0] != hash {
+        return false;
+    }
+    let mut records = match TreeEntry::parse_tree(data) {
+        Ok(records) => records,
+        Err(_) => return false,
+    };
+    match header.len() {
+        2 => get_str_hash(&records.iter().map(|entry| entry.hash.as_str()).collect::<String>()) == hash,
+        3 if header[2] == TREE_VERSION || header[2] == TREE_VERSION_UNPREFIXED => {
+            records.sort_by(|a, b| a.name.cmp(&b.name));
+            hash_records(&records, header[2]) == hash
+        }
+        _ => false,
+    }
+}
+
+/// Check that an object file, as it is stored, is the object named `hash`.
+pub fn check_object(hash: &str, data: &[u8]) -> bool {
+    if data.starts_with(format!("{} ", hash).as_bytes()) {
+        return match std::str::from_utf8(data) {
+            Ok(data) => check_tree_hash(hash, data),
+            Err(_) => false,
+        };
+    }
+    match try_decompress_data(data) {
+        Ok(data) => get_data_hash(&data) == hash,
+        Err(_) => false,
+    }
+}
+
+// entries are hashed in name order, with their mode and name, after a prefix
+// that keeps an empty tree from having the hash of an empty file
+fn hash_records(records: &[TreeEntry], version: &str) -> String {
+    let mut str = match version {
+        TREE_VERSION_UNPREFIXED => String::new(),
+        _ => String::from("tree\n"),
+    };
+    for entry in records {
+        str += &format!("{} {} {:o} {}\n", entry.hash, entry.object_type, entry.mode, entry.name);
+    }
+    get_str_hash(&str)
+}
+
+// where an object is stored, by its hash
+pub fn object_path(svc_path: &Path, hash: &str) -> PathBuf {
+    svc_path.join("objects").join(&hash[0..2]).join(&hash[2..])
+}
+
+/// Find the object a hash prefix names, like `Commit::find_commit` does for
+/// versions.
+pub fn find_object(svc_path: &Path, prefix: &str) -> Result<String, &'static str> {
+    if prefix.len() < 4 || !prefix.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err("object not found.");
+    }
+    let prefix = prefix.to_lowercase();
+    let mut hashes: Vec<String> = match fs::read_dir(svc_path.join("objects").join(&prefix[0..2])) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok())
+            .map(|entry| format!("{}{}", &prefix[0..2], entry.file_name().to_string_lossy()))
+            .filter(|hash| hash.starts_with(&prefix))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    match hashes.len() {
+        0 => Err("object not found."),
+        1 => Ok(hashes.remove(0)),
+        _ => Err("found more than one object matches."),
+    }
+}
+
+/// Read an object as it is stored, trees are text starting with their own
+/// hash and blobs are decompressed.
+pub fn read_object(svc_path: &Path, hash: &str) -> Result<(ObjectType, Vec<u8>), Error> {
+    let data = fs::read(object_path(svc_path, hash))?;
+    if data.starts_with(format!("{} ", hash).as_bytes()) {
+        return Ok((ObjectType::ObjectTree, data));
+    }
+    Ok((ObjectType::ObjectBlob, try_decompress_data(&data)?))
+}
+
+pub fn read_link_target(link_path: PathBuf) -> String {
+    fs::read_link(link_path).unwrap().to_str().unwrap().to_string()
+}
+
+#[cfg(unix)]
+pub fn get_file_mode(file_path: PathBuf) -> u32 {
+    use std::os::unix::fs::PermissionsExt;
+    fs::metadata(file_path).unwrap().permissions().mode() & 0o777
+}
+
+#[cfg(not(unix))]
+pub fn get_file_mode(file_path: PathBuf) -> u32 {
+    if fs::metadata(file_path).unwrap().permissions().readonly() {
+        0o444
+    } else {
+        MODE_FILE
+    }
+}
+
+#[cfg(unix)]
+fn set_file_mode(file_path: PathBuf, mode: u32) -> Result<(), Error> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(file_path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_file_mode(file_path: PathBuf, mode: u32) -> Result<(), Error> {
+    let mut permissions = fs::metadata(&file_path)?.permissions();
+    permissions.set_readonly(mode & 0o200 == 0);
+    fs::set_permissions(file_path, permissions)
+}
+
+#[cfg(unix)]
+fn create_symlink(target: PathBuf, link_path: PathBuf) -> Result<(), Error> {
+    std::os::unix::fs::symlink(target, link_path)
+}
+
+#[cfg(not(unix))]
+fn create_symlink(_target: PathBuf, _link_path: PathBuf) -> Result<(), Error> {
+    Err(Error::new(
+        std::io::ErrorKind::Unsupported,
+        "symbolic links are not supported on this platform",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(hash: &str, object_type: ObjectType, mode: u32, name: &str) -> TreeEntry {
+        TreeEntry {
+            hash: hash.to_string(),
+            object_type,
+            size: 1,
+            mode,
+            name: name.to_string(),
         }
-        tree
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn check_trees_of_every_version() {
+        let (blob, tree) = ("1".repeat(40), "2".repeat(40));
+        let records = vec![entry(&blob, ObjectType::ObjectBlob, 0o755, "a"), entry(&tree, ObjectType::ObjectTree, MODE_DIR, "b")];
+        let lines = format!("{} blob 1 755 a\n{} tree 1 755 b\n", blob, tree);
+        for version in [TREE_VERSION, TREE_VERSION_UNPREFIXED] {
+            let hash = hash_records(&records, version);
+            assert!(check_tree_hash(&hash, &format!("{} 2 {}\n{}", hash, version, lines)));
+            assert!(!check_tree_hash(&hash, &format!("{} 2 {}\n{}", hash, version, lines.replace("755 a", "644 a"))));
+        }
+
+        // trees without modes are hashed from their entry hashes, in order
+        let hash = get_str_hash(&format!("{}{}", blob, tree));
+        assert!(check_tree_hash(&hash, &format!("{} 2\n{} blob 1 a\n{} tree 1 b\n", hash, blob, tree)));
+        assert!(!check_tree_hash(&hash, &format!("{} 2\n{} tree 1 b\n{} blob 1 a\n", hash, tree, blob)));
+    }
+
+    #[test]
+    fn reject_malformed_trees() {
+        let hash = "3".repeat(40);
+        assert!(!check_tree_hash(&hash, &format!("{} 1 3\n{} blob 1 999 f\n", hash, hash)));
+        assert!(!check_tree_hash(&hash, &format!("{} 1 3\n{} blob x 644 f\n", hash, hash)));
+        assert!(!check_tree_hash(&hash, &format!("{} 1 3\n{} what 1 644 f\n", hash, hash)));
+        assert!(!check_tree_hash(&hash, &format!("{} 0 7\n", hash)));
+        assert!(TreeEntry::parse_tree(&format!("{} 1 3\n{} blob 1 f\n", hash, hash)).is_err());
+    }
+}
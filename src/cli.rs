@@ -19,25 +19,316 @@ pub enum Commands {
     /// show all versions log
     Log {},
 
+    /// show a version with the paths it changed, or a file of it as <version>:<path>
+    Show { object: String },
+
+    /// print a file as saved in a version
+    Cat {
+        /// file to print
+        path: String,
+
+        /// version to read the file from
+        #[arg(short, long, default_value = "HEAD")]
+        version: String
+    },
+
     /// check files status
     Status{},
 
     /// save current workplace
     Commit {
         /// commit message
-        #[arg(short, long)]
-        message: String
+        #[arg(short, long, required_unless_present = "amend")]
+        message: Option<String>,
+
+        /// replace the HEAD commit instead, keeping its message unless one is given
+        #[arg(long)]
+        amend: bool,
+
+        /// only save these files or directories, the rest is kept as in HEAD
+        paths: Vec<String>
     },
 
     /// switch to specific version
     Checkout { version: String },
 
+    /// restore files or directories from a version, leaving HEAD alone
+    Restore {
+        /// version to restore from, HEAD if omitted
+        #[arg(short, long)]
+        source: Option<String>,
+
+        /// files or directories to restore
+        #[arg(required = true)]
+        paths: Vec<String>
+    },
+
+    /// undo the changes of a version with a new commit
+    Revert {
+        /// version to revert
+        version: String,
+
+        /// message of the new commit
+        #[arg(short, long)]
+        message: Option<String>
+    },
+
+    /// mark conflicts of a merge or revert as resolved
+    Resolve {
+        /// take the version of HEAD
+        #[arg(long, conflicts_with = "theirs")]
+        ours: bool,
+
+        /// take the version being merged or reverted to
+        #[arg(long)]
+        theirs: bool,
+
+        /// conflicting files
+        #[arg(required = true)]
+        paths: Vec<String>
+    },
+
+    /// put local changes aside and reset the workspace to HEAD
+    Stash {
+        #[command(subcommand)]
+        command: Option<StashCommands>
+    },
+
+    /// merge another version into the current one
+    Merge {
+        /// version to merge
+        version: String,
+
+        /// message of the merge commit
+        #[arg(short, long)]
+        message: Option<String>
+    },
+
+    /// copy a version into another directory, leaving the workspace alone
+    Export {
+        /// version to export
+        version: String,
+
+        /// directory to write into, must be empty
+        dir: String,
+
+        /// only export these paths of the version
+        paths: Vec<String>
+    },
+
+    /// write a version into a tar or zip file
+    Archive {
+        /// version to archive
+        version: String,
+
+        /// tar, tar.gz or zip, guessed from the output file name if omitted
+        #[arg(long)]
+        format: Option<String>,
+
+        /// file to write
+        #[arg(short, long)]
+        output: String,
+
+        /// prepended to every path, e.g. "project/"
+        #[arg(long)]
+        prefix: Option<String>
+    },
+
     /// push to remote repo
-    Push {},
+    Push {
+        /// remote name, the default remote if omitted
+        remote: Option<String>
+    },
 
     /// pull from remote repo
-    Pull {},
+    Pull {
+        /// remote name, the default remote if omitted
+        remote: Option<String>
+    },
+
+    /// download commits from remote repo without touching the workspace
+    Fetch {
+        /// remote name, the default remote if omitted
+        remote: Option<String>
+    },
 
-    /// set remote repo url
+    /// copy a remote repo into a new directory
+    Clone {
+        /// url or path of the repo to clone
+        url: String,
+
+        /// directory to clone into
+        dir: Option<String>
+    },
+
+    /// pack commits into a single file, or import one
+    Bundle {
+        #[command(subcommand)]
+        command: BundleCommands
+    },
+
+    /// set the url of the default remote repo
     SetRemote { url: String },
+
+    /// manage remote repos
+    Remote {
+        #[command(subcommand)]
+        command: Option<RemoteCommands>
+    },
+
+    /// serve this repo over http for push and pull
+    Serve {
+        /// address to listen on
+        #[arg(long, default_value = "127.0.0.1:3906")]
+        bind: String,
+
+        /// speak the sync protocol over stdin/stdout instead
+        #[arg(long)]
+        stdio: bool,
+
+        /// repo to serve instead of the current one
+        path: Option<String>
+    },
+
+    /// replay the history of a git branch as commits of this empty repo
+    ImportGit {
+        /// git work tree or bare repository
+        path: String,
+
+        /// branch, tag or commit to import, HEAD if omitted
+        #[arg(short, long)]
+        branch: Option<String>
+    },
+
+    /// commit snapshot directories or archives as the history of this empty repo
+    ImportSnapshots {
+        /// directories or .tar, .tar.gz, .tgz and .zip files, one per version
+        #[arg(required = true)]
+        sources: Vec<String>,
+
+        /// date each snapshot by the date in its name, e.g. backup-2021-03-04,
+        /// instead of its modification time
+        #[arg(long)]
+        date_from_name: bool
+    },
+
+    /// write the whole history as a git fast-import stream
+    ExportGit {
+        /// file to write, stdout if omitted
+        #[arg(short, long)]
+        output: Option<String>,
+
+        /// git branch or ref to write the commits onto
+        #[arg(long = "ref", default_value = "master")]
+        git_ref: String
+    },
+
+    /// print an object by its hash
+    CatObject {
+        /// hash of the object, at least 4 characters of it
+        hash: String,
+
+        /// print the type of the object instead
+        #[arg(short = 't', group = "output")]
+        show_type: bool,
+
+        /// print the size of the object instead
+        #[arg(short, group = "output")]
+        size: bool,
+
+        /// print a tree as its entries
+        #[arg(short, group = "output")]
+        pretty: bool
+    },
+
+    /// list the entries of a tree
+    LsTree {
+        /// version or tree hash
+        object: String,
+
+        /// only list this path of the tree
+        path: Option<String>,
+
+        /// list the entries of sub trees too
+        #[arg(short, long)]
+        recursive: bool
+    },
+
+    /// print the object hash of a file
+    HashObject {
+        file: String,
+
+        /// save the file as an object too
+        #[arg(short, long)]
+        write: bool
+    },
+
+    /// show which .svcignore rule matches a path
+    CheckIgnore {
+        /// paths to check
+        #[arg(required = true)]
+        paths: Vec<String>
+    },
+}
+
+#[derive(Subcommand)]
+pub enum RemoteCommands {
+    /// list remote repos
+    List {},
+
+    /// add a remote repo
+    Add { name: String, url: String },
+
+    /// remove a remote repo
+    Remove { name: String },
+
+    /// rename a remote repo
+    Rename { old_name: String, new_name: String },
+
+    /// change the url of a remote repo
+    SetUrl { name: String, url: String },
+
+    /// push to and pull from this remote by default
+    SetDefault { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum BundleCommands {
+    /// pack commits into a bundle file
+    Create {
+        /// file to write
+        file: String,
+
+        /// a version, or <from>..<to> to leave out what <from> already has
+        range: Option<String>
+    },
+
+    /// check a bundle file and pull its commits
+    Unbundle { file: String },
+}
+
+#[derive(Subcommand)]
+pub enum StashCommands {
+    /// save local changes as a new entry and reset the workspace to HEAD
+    Push {
+        /// describe the entry
+        #[arg(short, long)]
+        message: Option<String>,
+
+        /// stash untracked files too
+        #[arg(short = 'u', long)]
+        include_untracked: bool
+    },
+
+    /// apply an entry to the workspace and drop it, the newest if omitted
+    Pop { stash: Option<String> },
+
+    /// list the entries, newest first
+    List {},
+
+    /// remove an entry, the newest if omitted
+    Drop { stash: Option<String> },
+
+    /// show the paths an entry changed, the newest if omitted
+    Show { stash: Option<String> },
 }
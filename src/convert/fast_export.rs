@@ -0,0 +1,116 @@
+use crate::bucket::{object_path, parse_date, Commit, ObjectType, TreeEntry};
+use chrono::{Local, Offset, TimeZone};
+use std::{
+    collections::HashMap,
+    io::{Error, Write},
+    path::{Path, PathBuf},
+};
+
+// used for commits made before authors were recorded
+const UNKNOWN_AUTHOR: &str = "unknown <unknown@localhost>";
+
+/// Write every commit of the log as a `git fast-import` stream onto `git_ref`,
+/// parents before children so the latest commit ends up as the branch tip.
+pub fn write_fast_export(svc_path: PathBuf, git_ref: &str, out: &mut impl Write) -> Result<usize, Error> {
+    let commits = Commit::read_from_log(svc_path.clone());
+    let mut marks: HashMap<String, usize> = HashMap::new();
+    let mut blob_marks: HashMap<String, usize> = HashMap::new();
+    let mut next_mark = 1;
+    let mut pending: Vec<&Commit> = commits.iter().collect();
+    while !pending.is_empty() {
+        // a commit is written once all of its parents are
+        let is_written = |parent: &String| {
+            marks.contains_key(parent) || !commits.iter().any(|commit| &commit.hash == parent)
+        };
+        let ready = pending
+            .iter()
+            .position(|commit| commit.parents().iter().all(is_written))
+            .ok_or_else(|| Error::other("the log has a cycle"))?;
+        let commit = pending.remove(ready);
+
+        let mut files = Vec::new();
+        collect_files(&svc_path, &commit.tree_hash, "", &mut files)?;
+        let mut file_commands = String::new();
+        for (path, entry) in files {
+            let mark = match blob_marks.get(&entry.hash) {
+                Some(mark) => *mark,
+                None => {
+                    let mark = next_mark;
+                    next_mark += 1;
+                    let data = TreeEntry::read_blob(object_path(&svc_path, &entry.hash))?;
+                    out.write_fmt(format_args!("blob\nmark :{}\ndata {}\n", mark, data.len()))?;
+                    out.write_all(&data)?;
+                    out.write_all(b"\n")?;
+                    blob_marks.insert(entry.hash.clone(), mark);
+                    mark
+                }
+            };
+            let mode = match entry.object_type {
+                ObjectType::ObjectLink => "120000",
+                _ if entry.mode & 0o111 != 0 => "100755",
+                _ => "100644",
+            };
+            file_commands += &format!("M {} :{} {}\n", mode, mark, quote_path(&path));
+        }
+
+        let parents: Vec<usize> = commit.parents().iter().filter_map(|parent| marks.get(parent).copied()).collect();
+        if parents.is_empty() {
+            // start a new root instead of continuing the branch
+            out.write_fmt(format_args!("reset {}\n", git_ref))?;
+        }
+        let author = if commit.author.is_empty() { UNKNOWN_AUTHOR } else { &commit.author };
+        let when = git_date(&commit.date)?;
+        let message = format!("{}\n", commit.message);
+        out.write_fmt(format_args!("commit {}\nmark :{}\n", git_ref, next_mark))?;
+        out.write_fmt(format_args!("author {} {}\ncommitter {} {}\n", author, when, author, when))?;
+        out.write_fmt(format_args!("data {}\n{}", message.len(), message))?;
+        for (i, parent) in parents.iter().enumerate() {
+            out.write_fmt(format_args!("{} :{}\n", if i == 0 { "from" } else { "merge" }, parent))?;
+        }
+        out.write_fmt(format_args!("deleteall\n{}\n", file_commands))?;
+        marks.insert(commit.hash.clone(), next_mark);
+        next_mark += 1;
+    }
+    out.write_all(b"done\n")?;
+    out.flush()?;
+    Ok(commits.len())
+}
+
+// every file and link of a tree by its path, git has no empty directories
+fn collect_files(svc_path: &Path, tree_hash: &str, prefix: &str, files: &mut Vec<(String, TreeEntry)>) -> Result<(), Error> {
+    for entry in TreeEntry::read_tree(object_path(svc_path, tree_hash))? {
+        let path = format!("{}{}", prefix, entry.name);
+        match entry.object_type {
+            ObjectType::ObjectTree => collect_files(svc_path, &entry.hash, &format!("{}/", path), files)?,
+            _ => files.push((path, entry)),
+        }
+    }
+    Ok(())
+}
+
+// svc dates are times to the minute, local unless they carry an offset, git
+// wants "<seconds> <+hhmm>"
+fn git_date(date: &str) -> Result<String, Error> {
+    let (naive, offset) = parse_date(date).ok_or_else(|| Error::other(format!("bad commit date '{}'", date)))?;
+    let (timestamp, offset) = match offset {
+        Some(offset) => (naive.and_utc().timestamp() - offset.local_minus_utc() as i64, offset.local_minus_utc()),
+        None => {
+            let local = Local
+                .from_local_datetime(&naive)
+                .earliest()
+                .ok_or_else(|| Error::other(format!("commit date '{}' does not exist here", date)))?;
+            (local.timestamp(), local.offset().fix().local_minus_utc())
+        }
+    };
+    let sign = if offset < 0 { '-' } else { '+' };
+    let offset = offset.abs();
+    Ok(format!("{} {}{:02}{:02}", timestamp, sign, offset / 3600, offset % 3600 / 60))
+}
+
+// paths with special characters are written as C-style quoted strings
+fn quote_path(path: &str) -> String {
+    if !path.contains(['"', '\\', '\n']) {
+        return path.to_string();
+    }
+    format!("\"{}\"", path.replace('\\', "\\\\").replace('"', "\\\"").replace('\n', "\\n"))
+}
@@ -0,0 +1,522 @@
+use flate2::read::ZlibDecoder;
+use std::{
+    collections::HashMap,
+    fs,
+    io::{Error, Read},
+    path::{Path, PathBuf},
+};
+
+/// A git repository read straight from its `.git` directory.
+pub struct GitRepo {
+    git_dir: PathBuf,
+    packs: Vec<Pack>,
+}
+
+struct Pack {
+    data: Vec<u8>,
+    // sorted object names with their offset in the pack
+    index: Vec<([u8; 20], u64)>,
+    // objects rebuilt from deltas, by offset
+    cache: HashMap<u64, (String, Vec<u8>)>,
+}
+
+pub struct GitCommit {
+    pub tree: String,
+    pub parents: Vec<String>,
+    pub author: String,
+    // seconds since the epoch and the author's offset from UTC in seconds
+    pub time: i64,
+    pub offset: i32,
+    pub message: String,
+}
+
+pub struct GitTreeEntry {
+    pub mode: u32,
+    pub name: String,
+    pub hash: String,
+}
+
+pub const GIT_MODE_TREE: u32 = 0o40000;
+pub const GIT_MODE_LINK: u32 = 0o120000;
+pub const GIT_MODE_SUBMODULE: u32 = 0o160000;
+
+impl GitRepo {
+    /// Open a work tree containing `.git`, or a bare repository.
+    pub fn open(path: &Path) -> Result<GitRepo, Error> {
+        let git_dir = if path.join(".git").is_dir() {
+            path.join(".git")
+        } else if path.join("objects").is_dir() && path.join("HEAD").is_file() {
+            path.to_path_buf()
+        } else {
+            return Err(Error::other(format!("'{}' is not a git repository", path.display())));
+        };
+        let mut packs = Vec::new();
+        if let Ok(entries) = fs::read_dir(git_dir.join("objects").join("pack")) {
+            for entry in entries {
+                let idx_path = entry?.path();
+                if idx_path.extension().is_some_and(|ext| ext == "idx") {
+                    packs.push(Pack::open(&idx_path)?);
+                }
+            }
+        }
+        Ok(GitRepo { git_dir, packs })
+    }
+
+    /// Resolve `HEAD`, a branch, a tag, a full ref name or a commit hash.
+    pub fn resolve(&self, name: &str) -> Result<String, Error> {
+        let candidates = if name == "HEAD" || name.starts_with("refs/") {
+            vec![name.to_string()]
+        } else {
+            vec![format!("refs/heads/{}", name), format!("refs/tags/{}", name)]
+        };
+        for candidate in &candidates {
+            if let Some(hash) = self.read_ref(candidate, 0)? {
+                return Ok(hash);
+            }
+        }
+        if name.len() == 40 && name.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Ok(name.to_lowercase());
+        }
+        Err(Error::other(format!("no branch, tag or commit named '{}' in the git repository", name)))
+    }
+
+    fn read_ref(&self, name: &str, depth: usize) -> Result<Option<String>, Error> {
+        if depth > 5 {
+            return Err(Error::other(format!("ref '{}' is a symbolic ref loop", name)));
+        }
+        if let Ok(data) = fs::read_to_string(self.git_dir.join(name)) {
+            let data = data.trim();
+            return match data.strip_prefix("ref: ") {
+                Some(target) => self.read_ref(target, depth + 1),
+                None => Ok(Some(data.to_string())),
+            };
+        }
+        if let Ok(data) = fs::read_to_string(self.git_dir.join("packed-refs")) {
+            for line in data.lines() {
+                if let Some((hash, ref_name)) = line.split_once(' ') {
+                    if ref_name == name && !hash.starts_with('#') {
+                        return Ok(Some(hash.to_string()));
+                    }
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Read an object as its type ("commit", "tree", "blob" or "tag") and data.
+    pub fn read_object(&mut self, hash: &str) -> Result<(String, Vec<u8>), Error> {
+        let loose_path = self.git_dir.join("objects").join(&hash[0..2]).join(&hash[2..]);
+        if let Ok(compressed) = fs::read(&loose_path) {
+            let mut data = Vec::new();
+            ZlibDecoder::new(&compressed[..]).read_to_end(&mut data)?;
+            let header_end = data
+                .iter()
+                .position(|byte| *byte == 0)
+                .ok_or_else(|| Error::other(format!("object {} is corrupted", hash)))?;
+            let header = String::from_utf8_lossy(&data[..header_end]).to_string();
+            let object_type = header.split(' ').next().unwrap_or("").to_string();
+            return Ok((object_type, data[header_end + 1..].to_vec()));
+        }
+        let name = parse_hex(hash)?;
+        for i in 0..self.packs.len() {
+            if let Ok(pos) = self.packs[i].index.binary_search_by(|(entry, _)| entry.cmp(&name)) {
+                let offset = self.packs[i].index[pos].1;
+                return self.read_packed(i, offset);
+            }
+        }
+        Err(Error::other(format!("object {} not found in the git repository", hash)))
+    }
+
+    fn read_packed(&mut self, pack: usize, offset: u64) -> Result<(String, Vec<u8>), Error> {
+        if let Some(object) = self.packs[pack].cache.get(&offset) {
+            return Ok(object.clone());
+        }
+        let data = &self.packs[pack].data;
+        let corrupted = || Error::other("git pack file is corrupted");
+        let mut pos = offset as usize;
+        let mut byte = *data.get(pos).ok_or_else(corrupted)?;
+        let kind = (byte >> 4) & 0x7;
+        let mut size = (byte & 0x0f) as usize;
+        let mut shift = 4;
+        while byte & 0x80 != 0 {
+            pos += 1;
+            byte = *data.get(pos).ok_or_else(corrupted)?;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+        }
+        pos += 1;
+        let object = match kind {
+            1..=4 => {
+                let object_type = ["commit", "tree", "blob", "tag"][kind as usize - 1];
+                (object_type.to_string(), inflate(&data[pos..], size)?)
+            }
+            6 => {
+                // the base is stored earlier in the same pack
+                let mut byte = *data.get(pos).ok_or_else(corrupted)?;
+                let mut distance = (byte & 0x7f) as u64;
+                while byte & 0x80 != 0 {
+                    pos += 1;
+                    byte = *data.get(pos).ok_or_else(corrupted)?;
+                    distance = ((distance + 1) << 7) | (byte & 0x7f) as u64;
+                }
+                pos += 1;
+                let delta = inflate(&data[pos..], size)?;
+                // a base always comes before the delta
+                let base_offset = offset.checked_sub(distance).filter(|_| distance > 0).ok_or_else(corrupted)?;
+                let (object_type, base) = self.read_packed(pack, base_offset)?;
+                (object_type, apply_delta(&base, &delta)?)
+            }
+            7 => {
+                let base_hash = to_hex(data.get(pos..pos + 20).ok_or_else(corrupted)?);
+                let delta = inflate(&data[pos + 20..], size)?;
+                let (object_type, base) = self.read_object(&base_hash)?;
+                (object_type, apply_delta(&base, &delta)?)
+            }
+            _ => return Err(corrupted()),
+        };
+        if kind >= 6 {
+            self.packs[pack].cache.insert(offset, object.clone());
+        }
+        Ok(object)
+    }
+
+    pub fn read_commit(&mut self, hash: &str) -> Result<GitCommit, Error> {
+        let (object_type, data) = self.read_object(hash)?;
+        let data = String::from_utf8_lossy(&data).to_string();
+        if object_type == "tag" {
+            // an annotated tag points at the commit
+            return match data.lines().next().and_then(|line| line.strip_prefix("object ")) {
+                Some(target) => self.read_commit(target),
+                None => Err(Error::other(format!("tag {} is corrupted", hash))),
+            };
+        }
+        if object_type != "commit" {
+            return Err(Error::other(format!("{} is a {}, not a commit", hash, object_type)));
+        }
+        let (headers, message) = data.split_once("\n\n").unwrap_or((&data, ""));
+        let mut commit = GitCommit {
+            tree: String::new(),
+            parents: Vec::new(),
+            author: String::new(),
+            time: 0,
+            offset: 0,
+            message: message.trim_end().to_string(),
+        };
+        for line in headers.lines() {
+            match line.split_once(' ') {
+                Some(("tree", hash)) => commit.tree = hash.to_string(),
+                Some(("parent", hash)) => commit.parents.push(hash.to_string()),
+                Some(("author", author)) => {
+                    // "Name <email> 1700000000 +0200"
+                    let mut fields = author.rsplitn(3, ' ');
+                    let zone = fields.next().unwrap_or("+0000");
+                    commit.time = fields.next().unwrap_or("0").parse().unwrap_or(0);
+                    commit.author = fields.next().unwrap_or("").to_string();
+                    let sign = if zone.starts_with('-') { -1 } else { 1 };
+                    let zone: i32 = zone.trim_start_matches(['+', '-']).parse().unwrap_or(0);
+                    commit.offset = sign * ((zone / 100) * 3600 + (zone % 100) * 60);
+                }
+                _ => (),
+            }
+        }
+        Ok(commit)
+    }
+
+    pub fn read_tree(&mut self, hash: &str) -> Result<Vec<GitTreeEntry>, Error> {
+        let (object_type, data) = self.read_object(hash)?;
+        if object_type != "tree" {
+            return Err(Error::other(format!("{} is a {}, not a tree", hash, object_type)));
+        }
+        let mut entries = Vec::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let corrupted = || Error::other(format!("tree {} is corrupted", hash));
+            let space = pos + data[pos..].iter().position(|byte| *byte == b' ').ok_or_else(corrupted)?;
+            let nul = space + data[space..].iter().position(|byte| *byte == 0).ok_or_else(corrupted)?;
+            let mode = u32::from_str_radix(&String::from_utf8_lossy(&data[pos..space]), 8).map_err(|_| corrupted())?;
+            let name = String::from_utf8_lossy(&data[space + 1..nul]).to_string();
+            let hash = to_hex(data.get(nul + 1..nul + 21).ok_or_else(corrupted)?);
+            entries.push(GitTreeEntry { mode, name, hash });
+            pos = nul + 21;
+        }
+        Ok(entries)
+    }
+}
+
+impl Pack {
+    // read a version 2 pack index and the pack it describes
+    fn open(idx_path: &Path) -> Result<Pack, Error> {
+        let idx = fs::read(idx_path)?;
+        let corrupted = || Error::other(format!("'{}' is corrupted", idx_path.display()));
+        if idx.len() < 8 + 256 * 4 || idx[0..4] != [0xff, b't', b'O', b'c'] || idx[4..8] != [0, 0, 0, 2] {
+            return Err(Error::other(format!("'{}' is not a version 2 pack index", idx_path.display())));
+        }
+        let read_u32 = |pos: usize| -> Result<u32, Error> {
+            let bytes = idx.get(pos..pos + 4).ok_or_else(corrupted)?;
+            Ok(u32::from_be_bytes(bytes.try_into().unwrap()))
+        };
+        let count = read_u32(8 + 255 * 4)? as usize;
+        let names = 8 + 256 * 4;
+        let offsets = names + count * 20 + count * 4;
+        let large_offsets = offsets + count * 4;
+        let mut index = Vec::with_capacity(count);
+        for i in 0..count {
+            let name: [u8; 20] = idx.get(names + i * 20..names + i * 20 + 20).ok_or_else(corrupted)?.try_into().unwrap();
+            let offset = read_u32(offsets + i * 4)?;
+            let offset = if offset & 0x8000_0000 != 0 {
+                let pos = large_offsets + (offset & 0x7fff_ffff) as usize * 8;
+                let bytes = idx.get(pos..pos + 8).ok_or_else(corrupted)?;
+                u64::from_be_bytes(bytes.try_into().unwrap())
+            } else {
+                offset as u64
+            };
+            index.push((name, offset));
+        }
+        let data = fs::read(idx_path.with_extension("pack"))?;
+        if data.len() < 12 || &data[0..4] != b"PACK" {
+            return Err(Error::other(format!("'{}' is not a pack file", idx_path.with_extension("pack").display())));
+        }
+        Ok(Pack {
+            data,
+            index,
+            cache: HashMap::new(),
+        })
+    }
+}
+
+fn inflate(data: &[u8], size: usize) -> Result<Vec<u8>, Error> {
+    let mut result = Vec::with_capacity(size);
+    ZlibDecoder::new(data).read_to_end(&mut result)?;
+    if result.len() != size {
+        return Err(Error::other("git pack file is corrupted"));
+    }
+    Ok(result)
+}
+
+// rebuild an object from its base and a git delta
+fn apply_delta(base: &[u8], delta: &[u8]) -> Result<Vec<u8>, Error> {
+    let corrupted = || Error::other("git delta is corrupted");
+    let mut pos = 0;
+    let read_size = |pos: &mut usize| -> Result<usize, Error> {
+        let mut size = 0;
+        let mut shift = 0;
+        loop {
+            let byte = *delta.get(*pos).ok_or_else(corrupted)?;
+            *pos += 1;
+            size |= ((byte & 0x7f) as usize) << shift;
+            shift += 7;
+            if byte & 0x80 == 0 {
+                return Ok(size);
+            }
+        }
+    };
+    let base_size = read_size(&mut pos)?;
+    let result_size = read_size(&mut pos)?;
+    if base_size != base.len() {
+        return Err(corrupted());
+    }
+    let mut result = Vec::with_capacity(result_size);
+    while pos < delta.len() {
+        let op = delta[pos];
+        pos += 1;
+        if op & 0x80 != 0 {
+            // copy a range of the base
+            let mut values = [0usize; 7];
+            for (bit, value) in values.iter_mut().enumerate() {
+                if op & (1 << bit) != 0 {
+                    *value = *delta.get(pos).ok_or_else(corrupted)? as usize;
+                    pos += 1;
+                }
+            }
+            let offset = values[0] | values[1] << 8 | values[2] << 16 | values[3] << 24;
+            let mut size = values[4] | values[5] << 8 | values[6] << 16;
+            if size == 0 {
+                size = 0x10000;
+            }
+            result.extend_from_slice(base.get(offset..offset + size).ok_or_else(corrupted)?);
+        } else if op != 0 {
+            // insert the next bytes of the delta
+            let size = op as usize;
+            result.extend_from_slice(delta.get(pos..pos + size).ok_or_else(corrupted)?);
+            pos += size;
+        } else {
+            return Err(corrupted());
+        }
+    }
+    if result.len() != result_size {
+        return Err(corrupted());
+    }
+    Ok(result)
+}
+
+fn parse_hex(hash: &str) -> Result<[u8; 20], Error> {
+    let invalid = || Error::other(format!("invalid object hash '{}'", hash));
+    if hash.len() != 40 {
+        return Err(invalid());
+    }
+    let mut bytes = [0u8; 20];
+    for (i, byte) in bytes.iter_mut().enumerate() {
+        *byte = u8::from_str_radix(&hash[i * 2..i * 2 + 2], 16).map_err(|_| invalid())?;
+    }
+    Ok(bytes)
+}
+
+fn to_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{:02x}", byte)).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::{write::ZlibEncoder, Compression};
+    use std::io::Write;
+
+    fn deflate(data: &[u8]) -> Vec<u8> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    // the type and size header of a packed object
+    fn object_header(kind: u8, size: usize) -> Vec<u8> {
+        let mut header = vec![(kind << 4) | (size & 0x0f) as u8];
+        let mut size = size >> 4;
+        while size > 0 {
+            *header.last_mut().unwrap() |= 0x80;
+            header.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        header
+    }
+
+    fn delta_size(size: usize) -> Vec<u8> {
+        let mut bytes = vec![(size & 0x7f) as u8];
+        let mut size = size >> 7;
+        while size > 0 {
+            *bytes.last_mut().unwrap() |= 0x80;
+            bytes.push((size & 0x7f) as u8);
+            size >>= 7;
+        }
+        bytes
+    }
+
+    // a delta turning "hello world\n" into "hello git world\n"
+    fn hello_delta() -> Vec<u8> {
+        let mut delta = delta_size(12);
+        delta.extend(delta_size(16));
+        // copy 6 bytes from offset 0
+        delta.extend([0x80 | 0x10, 6]);
+        delta.extend([4]);
+        delta.extend(b"git ");
+        // copy 6 bytes from offset 6
+        delta.extend([0x80 | 0x01 | 0x10, 6, 6]);
+        delta
+    }
+
+    fn repo_with_pack(data: Vec<u8>, index: Vec<([u8; 20], u64)>) -> GitRepo {
+        let pack = Pack { data, index, cache: HashMap::new() };
+        GitRepo { git_dir: PathBuf::from("/nonexistent"), packs: vec![pack] }
+    }
+
+    #[test]
+    fn apply_copy_and_insert() {
+        assert_eq!(apply_delta(b"hello world\n", &hello_delta()).unwrap(), b"hello git world\n");
+        // a copy without a size copies 0x10000 bytes
+        let base = vec![7u8; 0x10000];
+        let mut delta = delta_size(base.len());
+        delta.extend(delta_size(base.len()));
+        delta.push(0x80);
+        assert_eq!(apply_delta(&base, &delta).unwrap(), base);
+    }
+
+    #[test]
+    fn reject_corrupted_deltas() {
+        let delta = hello_delta();
+        // wrong base size
+        assert!(apply_delta(b"hello", &delta).is_err());
+        // truncated
+        assert!(apply_delta(b"hello world\n", &delta[..delta.len() - 1]).is_err());
+        // copy past the end of the base
+        let mut bad = delta_size(12);
+        bad.extend(delta_size(16));
+        bad.extend([0x80 | 0x01 | 0x10, 10, 16]);
+        assert!(apply_delta(b"hello world\n", &bad).is_err());
+        // the reserved op
+        let mut bad = delta_size(12);
+        bad.extend(delta_size(0));
+        bad.push(0);
+        assert!(apply_delta(b"hello world\n", &bad).is_err());
+        assert!(apply_delta(b"", &[]).is_err());
+    }
+
+    #[test]
+    fn read_packed_objects_and_deltas() {
+        let base = b"hello world\n";
+        let delta = hello_delta();
+        let mut data = b"PACK\0\0\0\x02\0\0\0\x03".to_vec();
+        let base_offset = data.len() as u64;
+        data.extend(object_header(3, base.len()));
+        data.extend(deflate(base));
+        // a delta against the object `distance` bytes before it
+        let ofs_offset = data.len() as u64;
+        let distance = ofs_offset - base_offset;
+        data.extend(object_header(6, delta.len()));
+        assert!(distance < 0x80);
+        data.push(distance as u8);
+        data.extend(deflate(&delta));
+        // a delta against an object named by its hash
+        let ref_offset = data.len() as u64;
+        let base_name = [1u8; 20];
+        data.extend(object_header(7, delta.len()));
+        data.extend(base_name);
+        data.extend(deflate(&delta));
+
+        let mut repo = repo_with_pack(data, vec![(base_name, base_offset), ([2u8; 20], ref_offset)]);
+        assert_eq!(repo.read_packed(0, base_offset).unwrap(), ("blob".to_string(), base.to_vec()));
+        let expected = ("blob".to_string(), b"hello git world\n".to_vec());
+        assert_eq!(repo.read_packed(0, ofs_offset).unwrap(), expected);
+        assert_eq!(repo.read_packed(0, ref_offset).unwrap(), expected);
+        // from the cache this time
+        assert_eq!(repo.read_packed(0, ofs_offset).unwrap(), expected);
+        assert_eq!(repo.read_object(&to_hex(&[2u8; 20])).unwrap(), expected);
+        assert!(repo.read_object(&to_hex(&[3u8; 20])).is_err());
+    }
+
+    #[test]
+    fn reject_corrupted_packs() {
+        let base = b"hello world\n";
+        let mut data = b"PACK\0\0\0\x02\0\0\0\x02".to_vec();
+        let offset = data.len() as u64;
+        data.extend(object_header(3, base.len() + 1));
+        data.extend(deflate(base));
+        // a delta pointing at itself
+        let self_offset = data.len() as u64;
+        data.extend(object_header(6, 4));
+        data.push(0);
+        data.extend(deflate(b"abcd"));
+        let end = data.len() as u64;
+
+        let mut repo = repo_with_pack(data, Vec::new());
+        // wrong size
+        assert!(repo.read_packed(0, offset).is_err());
+        assert!(repo.read_packed(0, self_offset).is_err());
+        assert!(repo.read_packed(0, end).is_err());
+    }
+
+    #[test]
+    fn read_commit_headers() {
+        let commit = b"tree 0123456789012345678901234567890123456789\nparent 1111111111111111111111111111111111111111\nauthor Jane Doe <jane@example.com> 1700000000 -0130\ncommitter x <x> 1 +0000\n\nfix things\n\nmore\n";
+        let mut data = b"PACK\0\0\0\x02\0\0\0\x01".to_vec();
+        let offset = data.len() as u64;
+        data.extend(object_header(1, commit.len()));
+        data.extend(deflate(commit));
+        let mut repo = repo_with_pack(data, vec![([5u8; 20], offset)]);
+        let commit = repo.read_commit(&to_hex(&[5u8; 20])).unwrap();
+        assert_eq!(commit.tree, "0123456789012345678901234567890123456789");
+        assert_eq!(commit.parents, vec!["1".repeat(40)]);
+        assert_eq!(commit.author, "Jane Doe <jane@example.com>");
+        assert_eq!(commit.time, 1700000000);
+        assert_eq!(commit.offset, -5400);
+        assert_eq!(commit.message, "fix things\n\nmore");
+    }
+}
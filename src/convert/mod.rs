@@ -0,0 +1,228 @@
+mod fast_export;
+mod git;
+mod snapshots;
+
+use crate::bucket::{check_svc_repo, check_workspace, Commit, ObjectType, RepoMeta, Tree, NO_PARENT, TreeEntry, MODE_DIR, MODE_FILE, MODE_LINK};
+use crate::util::get_data_hash;
+use chrono::{DateTime, FixedOffset};
+use git::{GitRepo, GIT_MODE_LINK, GIT_MODE_SUBMODULE, GIT_MODE_TREE};
+use fast_export::write_fast_export;
+use snapshots::Snapshot;
+use std::{
+    collections::HashMap,
+    env,
+    fs::{self, File},
+    io::{self, BufWriter, Error},
+    path::PathBuf,
+    process,
+};
+
+pub fn import_git(path: &str, branch: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let path = env::current_dir().unwrap().join(path);
+            if let Err(err) = import_git_history(svc_path, path, branch.as_deref().unwrap_or("HEAD")) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn import_snapshots(sources: &[String], date_from_name: bool) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = import_snapshot_history(svc_path, sources, date_from_name) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn export_git(output: &Option<String>, git_ref: &str) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let git_ref = if git_ref.starts_with("refs/") {
+                git_ref.to_string()
+            } else {
+                format!("refs/heads/{}", git_ref)
+            };
+            let result = match output {
+                Some(output) => File::create(output).and_then(|file| {
+                    let mut out = BufWriter::new(file);
+                    write_fast_export(svc_path, &git_ref, &mut out)
+                }),
+                None => write_fast_export(svc_path, &git_ref, &mut io::stdout().lock()),
+            };
+            match result {
+                // the stream may be on stdout, report on stderr
+                Ok(count) => eprintln!("export {} commit(s) onto {}.", count, git_ref),
+                Err(err) => {
+                    eprintln!("error: {}", err);
+                    process::exit(1);
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+// svc objects already made from git objects, by git hash
+#[derive(Default)]
+struct Converted {
+    blobs: HashMap<String, (String, u64)>,
+    trees: HashMap<String, (String, u64)>,
+}
+
+// replay the first-parent history of a git branch as svc commits
+fn import_git_history(svc_path: PathBuf, path: PathBuf, branch: &str) -> Result<(), Error> {
+    if !Commit::read_from_log(svc_path.clone()).is_empty() {
+        return Err(Error::other("history can only be imported into a repo without commits"));
+    }
+    let mut repo = GitRepo::open(&path)?;
+    let mut hash = repo.resolve(branch)?;
+    let mut chain = Vec::new();
+    loop {
+        let commit = repo.read_commit(&hash)?;
+        let parent = commit.parents.first().cloned();
+        chain.push(commit);
+        match parent {
+            Some(parent) => hash = parent,
+            None => break,
+        }
+    }
+
+    let mut converted = Converted::default();
+    let mut commits: Vec<Commit> = Vec::new();
+    for git_commit in chain.into_iter().rev() {
+        let (tree_hash, _) = convert_tree(&mut repo, &svc_path, &git_commit.tree, &mut converted)?;
+        let date = DateTime::from_timestamp(git_commit.time, 0)
+            .zip(FixedOffset::east_opt(git_commit.offset))
+            .map(|(date, offset)| date.with_timezone(&offset).format("%Y-%m-%d %H:%M%z").to_string())
+            .ok_or_else(|| Error::other(format!("bad date in git commit '{}'", git_commit.message)))?;
+        let parent_hash = match commits.last() {
+            Some(parent) => parent.hash.clone(),
+            None => NO_PARENT.to_string(),
+        };
+        commits.push(Commit::with_author(git_commit.message, parent_hash, tree_hash, date, git_commit.author));
+    }
+
+    let latest = write_history(svc_path, &commits)?;
+    println!("import {} commit(s) from git, switch to commit {}.", commits.len(), latest.hash);
+    Ok(())
+}
+
+// commit each snapshot on top of the one before, oldest first
+fn import_snapshot_history(svc_path: PathBuf, sources: &[String], date_from_name: bool) -> Result<(), Error> {
+    if !Commit::read_from_log(svc_path.clone()).is_empty() {
+        return Err(Error::other("history can only be imported into a repo without commits"));
+    }
+    // read every date first, so a bad name fails before anything is saved
+    let current_dir = env::current_dir()?;
+    let mut snapshots = sources
+        .iter()
+        .map(|source| Snapshot::open(current_dir.join(source), date_from_name))
+        .collect::<Result<Vec<Snapshot>, Error>>()?;
+    // snapshots of the same date keep the order they were given in
+    snapshots.sort_by(|a, b| a.date.cmp(&b.date));
+
+    let mut commits: Vec<Commit> = Vec::new();
+    for snapshot in snapshots {
+        let tree_hash = snapshot.save_tree(svc_path.clone())?;
+        let parent_hash = match commits.last() {
+            Some(parent) if parent.tree_hash == tree_hash => {
+                println!("snapshot {} is the same as the one before, skip it.", snapshot.name);
+                continue;
+            }
+            Some(parent) => parent.hash.clone(),
+            None => NO_PARENT.to_string(),
+        };
+        let message = format!("import snapshot {}", snapshot.name);
+        commits.push(Commit::with_author(message, parent_hash, tree_hash, snapshot.date, String::new()));
+    }
+
+    let latest = write_history(svc_path, &commits)?;
+    println!("import {} snapshot(s), switch to commit {}.", commits.len(), latest.hash);
+    Ok(())
+}
+
+// make imported commits the whole log and switch the workspace to the last one
+fn write_history(svc_path: PathBuf, commits: &[Commit]) -> Result<Commit, Error> {
+    let latest = commits.last().unwrap().clone();
+    // nothing was committed before, so every file in the way is untracked
+    check_workspace(svc_path.clone(), None, &latest.tree_hash, "import")?;
+    Commit::write_log(commits, svc_path.clone())?;
+    fs::write(svc_path.join("latest"), &latest.hash)?;
+    Commit::restore_tree(svc_path.parent().unwrap().to_path_buf(), svc_path.clone(), latest.tree_hash.clone())?;
+    Commit::reset_head(svc_path.clone(), latest.hash.clone());
+    RepoMeta::touch_updated_at(svc_path)?;
+    Ok(latest)
+}
+
+fn convert_tree(repo: &mut GitRepo, svc_path: &PathBuf, git_hash: &str, converted: &mut Converted) -> Result<(String, u64), Error> {
+    if let Some(tree) = converted.trees.get(git_hash) {
+        return Ok(tree.clone());
+    }
+    let mut records = Vec::new();
+    for entry in repo.read_tree(git_hash)? {
+        let record = match entry.mode {
+            GIT_MODE_TREE => {
+                let (hash, size) = convert_tree(repo, svc_path, &entry.hash, converted)?;
+                TreeEntry {
+                    hash,
+                    object_type: ObjectType::ObjectTree,
+                    size,
+                    mode: MODE_DIR,
+                    name: entry.name,
+                }
+            }
+            GIT_MODE_SUBMODULE => {
+                eprintln!("warning: submodule '{}' is skipped.", entry.name);
+                continue;
+            }
+            mode => {
+                let (hash, size) = match converted.blobs.get(&entry.hash) {
+                    Some(blob) => blob.clone(),
+                    None => {
+                        let (_, data) = repo.read_object(&entry.hash)?;
+                        let hash = get_data_hash(&data);
+                        TreeEntry::write_blob_data(&data, svc_path.clone(), &hash)?;
+                        converted.blobs.insert(entry.hash.clone(), (hash.clone(), data.len() as u64));
+                        (hash, data.len() as u64)
+                    }
+                };
+                let (object_type, mode) = if mode == GIT_MODE_LINK {
+                    (ObjectType::ObjectLink, MODE_LINK)
+                } else if mode & 0o111 != 0 {
+                    (ObjectType::ObjectBlob, 0o755)
+                } else {
+                    (ObjectType::ObjectBlob, MODE_FILE)
+                };
+                TreeEntry {
+                    hash,
+                    object_type,
+                    size,
+                    mode,
+                    name: entry.name,
+                }
+            }
+        };
+        records.push(record);
+    }
+    let size = records.iter().map(|record| record.size).sum();
+    let tree = Tree::from_records(records, size, svc_path.clone())?;
+    converted.trees.insert(git_hash.to_string(), (tree.hash.clone(), tree.size));
+    Ok((tree.hash, tree.size))
+}
@@ -0,0 +1,256 @@
+use crate::bucket::{ArchiveFormat, Ignore, ObjectType, Tree, TreeEntry, MODE_DIR, MODE_FILE, MODE_LINK};
+use crate::util::get_data_hash;
+use chrono::{DateTime, Local, NaiveDate, NaiveTime};
+use flate2::read::GzDecoder;
+use std::{
+    collections::BTreeMap,
+    fs::{self, File},
+    io::{BufReader, Error, Read, Seek},
+    path::{Path, PathBuf},
+};
+use zip::ZipArchive;
+
+/// A directory, tar or zip file holding the whole project at some point in time.
+pub struct Snapshot {
+    pub path: PathBuf,
+    pub name: String,
+    pub date: String,
+}
+
+impl Snapshot {
+    pub fn open(path: PathBuf, date_from_name: bool) -> Result<Snapshot, Error> {
+        let name = match path.file_name() {
+            Some(name) => name.to_string_lossy().to_string(),
+            None => return Err(Error::other(format!("'{}' has no name", path.display()))),
+        };
+        let metadata = fs::metadata(&path)?;
+        if !metadata.is_dir() && ArchiveFormat::from_file_name(&name).is_none() {
+            return Err(Error::other(format!("'{}' is neither a directory nor a tar or zip file", path.display())));
+        }
+        let date = if date_from_name {
+            match parse_name_date(&name) {
+                Some(date) => date,
+                None => return Err(Error::other(format!("no date found in the name of '{}'", path.display()))),
+            }
+        } else {
+            DateTime::<Local>::from(metadata.modified()?).format("%Y-%m-%d %H:%M").to_string()
+        };
+        Ok(Snapshot { path, name, date })
+    }
+
+    /// Save every file of the snapshot as objects, without extracting anything,
+    /// and return the hash of its root tree.
+    pub fn save_tree(&self, svc_path: PathBuf) -> Result<String, Error> {
+        if self.path.is_dir() {
+            // a directory is saved like a workspace, with its own .svcignore rules
+            let exclude = Ignore::new(self.path.clone());
+            return Ok(Tree::new(self.path.clone(), svc_path, &exclude).hash);
+        }
+        let mut entries = BTreeMap::new();
+        let file = BufReader::new(File::open(&self.path)?);
+        match ArchiveFormat::from_file_name(&self.name) {
+            Some(ArchiveFormat::Tar) => read_tar(tar::Archive::new(file), &svc_path, &mut entries)?,
+            Some(ArchiveFormat::TarGz) => read_tar(tar::Archive::new(GzDecoder::new(file)), &svc_path, &mut entries)?,
+            Some(ArchiveFormat::Zip) => read_zip(file, &svc_path, &mut entries)?,
+            None => return Err(Error::other(format!("'{}' is not a tar or zip file", self.name))),
+        }
+        Ok(Tree::from_entries(svc_path, &strip_top_dir(entries))?.hash)
+    }
+}
+
+fn read_tar<R: Read>(mut archive: tar::Archive<R>, svc_path: &Path, entries: &mut BTreeMap<String, TreeEntry>) -> Result<(), Error> {
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let raw_path = entry.path()?.to_string_lossy().to_string();
+        let path = match clean_path(&raw_path) {
+            Some(path) => path,
+            None => continue,
+        };
+        let entry_type = entry.header().entry_type();
+        let record = if entry_type.is_dir() {
+            dir_entry()
+        } else if entry_type.is_symlink() {
+            let target = entry.link_name()?.map(|target| target.to_string_lossy().to_string()).unwrap_or_default();
+            save_blob(svc_path, target.as_bytes(), ObjectType::ObjectLink, MODE_LINK)?
+        } else if entry_type.is_hard_link() {
+            // the linked file always comes earlier in the archive
+            let target = entry.link_name()?.map(|target| target.to_string_lossy().to_string()).unwrap_or_default();
+            match clean_path(&target).and_then(|target| entries.get(&target)) {
+                Some(record) => record.clone(),
+                None => {
+                    eprintln!("warning: '{}' links to missing '{}', skip it.", raw_path, target);
+                    continue;
+                }
+            }
+        } else if entry_type.is_file() {
+            let mode = file_mode(entry.header().mode()?);
+            let mut data = Vec::new();
+            entry.read_to_end(&mut data)?;
+            save_blob(svc_path, &data, ObjectType::ObjectBlob, mode)?
+        } else {
+            eprintln!("warning: '{}' is not a file, directory or link, skip it.", raw_path);
+            continue;
+        };
+        entries.insert(path, record);
+    }
+    Ok(())
+}
+
+fn read_zip<R: Read + Seek>(reader: R, svc_path: &Path, entries: &mut BTreeMap<String, TreeEntry>) -> Result<(), Error> {
+    let mut archive = ZipArchive::new(reader)?;
+    for i in 0..archive.len() {
+        let mut file = archive.by_index(i)?;
+        let path = match clean_path(file.name()) {
+            Some(path) => path,
+            None => continue,
+        };
+        let record = if file.is_dir() {
+            dir_entry()
+        } else {
+            let mut data = Vec::new();
+            file.read_to_end(&mut data)?;
+            if file.is_symlink() {
+                save_blob(svc_path, &data, ObjectType::ObjectLink, MODE_LINK)?
+            } else {
+                let mode = file_mode(file.unix_mode().unwrap_or(0));
+                save_blob(svc_path, &data, ObjectType::ObjectBlob, mode)?
+            }
+        };
+        entries.insert(path, record);
+    }
+    Ok(())
+}
+
+fn save_blob(svc_path: &Path, data: &[u8], object_type: ObjectType, mode: u32) -> Result<TreeEntry, Error> {
+    let hash = get_data_hash(data);
+    TreeEntry::write_blob_data(data, svc_path.to_path_buf(), &hash)?;
+    Ok(TreeEntry {
+        hash,
+        object_type,
+        size: data.len() as u64,
+        mode,
+        name: String::new(),
+    })
+}
+
+// only marks the directory, Tree::from_entries builds the real tree
+fn dir_entry() -> TreeEntry {
+    TreeEntry {
+        hash: String::new(),
+        object_type: ObjectType::ObjectTree,
+        size: 0,
+        mode: MODE_DIR,
+        name: String::new(),
+    }
+}
+
+// archives made elsewhere may have no permission bits at all
+fn file_mode(mode: u32) -> u32 {
+    match mode & 0o777 {
+        0 => MODE_FILE,
+        mode => mode,
+    }
+}
+
+// a relative path with "/" separators, None for paths leaving the root or inside .svc
+fn clean_path(path: &str) -> Option<String> {
+    let mut parts = Vec::new();
+    for part in path.split(['/', '\\']) {
+        match part {
+            "" | "." => (),
+            ".." | ".svc" => {
+                eprintln!("warning: '{}' is skipped.", path);
+                return None;
+            }
+            part => parts.push(part),
+        }
+    }
+    if parts.is_empty() {
+        return None;
+    }
+    Some(parts.join("/"))
+}
+
+// an archive holding a single directory, like "project-1.0/", is that directory's snapshot
+fn strip_top_dir(entries: BTreeMap<String, TreeEntry>) -> BTreeMap<String, TreeEntry> {
+    let top = match entries.keys().next() {
+        Some(path) => path.split('/').next().unwrap().to_string(),
+        None => return entries,
+    };
+    let prefix = format!("{}/", top);
+    let single_dir = entries.iter().all(|(path, entry)| {
+        path.starts_with(&prefix) || (*path == top && entry.object_type == ObjectType::ObjectTree)
+    });
+    if !single_dir || !entries.keys().any(|path| path.starts_with(&prefix)) {
+        return entries;
+    }
+    entries
+        .into_iter()
+        .filter_map(|(path, entry)| path.strip_prefix(&prefix).map(|rest| (rest.to_string(), entry)))
+        .collect()
+}
+
+// find a date like 2021-03-04, 2021_03_04 or 20210304 in a name, optionally
+// followed by a time like 15-30 or 1530
+fn parse_name_date(name: &str) -> Option<String> {
+    let groups: Vec<&str> = name.split(|c: char| !c.is_ascii_digit()).filter(|group| !group.is_empty()).collect();
+    for i in 0..groups.len() {
+        let (date, rest): (String, Vec<&str>) = match groups[i].len() {
+            // a time may directly follow the date, as in 202103041530
+            8 | 12 | 14 => {
+                let (date, time) = groups[i].split_at(8);
+                let rest = [time].into_iter().filter(|time| !time.is_empty()).chain(groups[i + 1..].iter().copied());
+                (date.to_string(), rest.collect())
+            }
+            4 if i + 2 < groups.len() && groups[i + 1].len() == 2 && groups[i + 2].len() == 2 => {
+                (groups[i..i + 3].concat(), groups[i + 3..].to_vec())
+            }
+            _ => continue,
+        };
+        let date = match NaiveDate::parse_from_str(&date, "%Y%m%d") {
+            Ok(date) => date,
+            Err(_) => continue,
+        };
+        let time = match rest[..] {
+            [time, ..] if time.len() == 4 || time.len() == 6 => NaiveTime::parse_from_str(&time[..4], "%H%M").ok(),
+            [hour, minute, ..] if hour.len() == 2 && minute.len() == 2 => {
+                NaiveTime::parse_from_str(&format!("{}{}", hour, minute), "%H%M").ok()
+            }
+            _ => None,
+        };
+        return Some(date.and_time(time.unwrap_or_default()).format("%Y-%m-%d %H:%M").to_string());
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_dates_in_names() {
+        assert_eq!(parse_name_date("backup-2021-03-04").as_deref(), Some("2021-03-04 00:00"));
+        assert_eq!(parse_name_date("backup_2021_03_04.tar.gz").as_deref(), Some("2021-03-04 00:00"));
+        assert_eq!(parse_name_date("20210304").as_deref(), Some("2021-03-04 00:00"));
+        assert_eq!(parse_name_date("site-1.2-2021-03-04").as_deref(), Some("2021-03-04 00:00"));
+    }
+
+    #[test]
+    fn parse_times_in_names() {
+        assert_eq!(parse_name_date("20210304-1530").as_deref(), Some("2021-03-04 15:30"));
+        assert_eq!(parse_name_date("202103041530.zip").as_deref(), Some("2021-03-04 15:30"));
+        assert_eq!(parse_name_date("20210304153059").as_deref(), Some("2021-03-04 15:30"));
+        assert_eq!(parse_name_date("2021-03-04_15-30").as_deref(), Some("2021-03-04 15:30"));
+        // not a valid time, only the date is kept
+        assert_eq!(parse_name_date("2021-03-04-9930").as_deref(), Some("2021-03-04 00:00"));
+        assert_eq!(parse_name_date("2021-03-04-v1.2").as_deref(), Some("2021-03-04 00:00"));
+    }
+
+    #[test]
+    fn reject_names_without_dates() {
+        assert_eq!(parse_name_date("backup"), None);
+        assert_eq!(parse_name_date("project-1.0"), None);
+        assert_eq!(parse_name_date("2021-13-40"), None);
+        assert_eq!(parse_name_date("12345678"), None);
+    }
+}
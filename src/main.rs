@@ -1,11 +1,13 @@
 mod cli;
 mod bucket;
+mod convert;
 mod remote;
 mod util;
 
 use bucket::*;
 use clap::Parser;
 use cli::*;
+use convert::*;
 use remote::*;
 
 fn main() {
@@ -17,18 +19,88 @@ fn main() {
 
         Some(Commands::Log {}) => log(),
 
+        Some(Commands::Show { object }) => show(object),
+
+        Some(Commands::Cat { path, version }) => cat(path, version),
+
         Some(Commands::Status {}) => status(),
 
-        Some(Commands::Commit { message }) => commit(message),
+        Some(Commands::Commit { message, amend, paths }) => commit(message, *amend, paths),
 
         Some(Commands::Checkout { version }) => checkout(version),
 
-        Some(Commands::Pull {}) => pull(),
+        Some(Commands::Restore { source, paths }) => restore(source, paths),
+
+        Some(Commands::Revert { version, message }) => revert(version, message),
+
+        Some(Commands::Resolve { ours, theirs, paths }) => resolve(*ours, *theirs, paths),
+
+        Some(Commands::Stash { command }) => match command {
+            Some(StashCommands::Push { message, include_untracked }) => stash_push(message, *include_untracked),
+
+            Some(StashCommands::Pop { stash }) => stash_pop(stash),
+
+            Some(StashCommands::List {}) => stash_list(),
+
+            Some(StashCommands::Drop { stash }) => stash_drop(stash),
+
+            Some(StashCommands::Show { stash }) => stash_show(stash),
+
+            None => stash_push(&None, false),
+        },
+
+        Some(Commands::Merge { version, message }) => merge(version, message),
+
+        Some(Commands::Export { version, dir, paths }) => export(version, dir, paths),
+
+        Some(Commands::Archive { version, format, output, prefix }) => archive(version, format, output, prefix),
+
+        Some(Commands::Pull { remote }) => pull(remote),
+
+        Some(Commands::Push { remote }) => push(remote),
+
+        Some(Commands::Fetch { remote }) => fetch(remote),
 
-        Some(Commands::Push {}) => push(),
+        Some(Commands::Clone { url, dir }) => clone(url, dir),
+
+        Some(Commands::Bundle { command }) => match command {
+            BundleCommands::Create { file, range } => bundle_create(file, range),
+
+            BundleCommands::Unbundle { file } => bundle_unbundle(file),
+        },
 
         Some(Commands::SetRemote { url }) => set_remote(url),
 
+        Some(Commands::Remote { command }) => match command {
+            Some(RemoteCommands::Add { name, url }) => remote_add(name, url),
+
+            Some(RemoteCommands::Remove { name }) => remote_remove(name),
+
+            Some(RemoteCommands::Rename { old_name, new_name }) => remote_rename(old_name, new_name),
+
+            Some(RemoteCommands::SetUrl { name, url }) => remote_set_url(name, url),
+
+            Some(RemoteCommands::SetDefault { name }) => remote_set_default(name),
+
+            Some(RemoteCommands::List {}) | None => remote_list(),
+        },
+
+        Some(Commands::Serve { bind, stdio, path }) => serve(bind, *stdio, path),
+
+        Some(Commands::ImportGit { path, branch }) => import_git(path, branch),
+
+        Some(Commands::ImportSnapshots { sources, date_from_name }) => import_snapshots(sources, *date_from_name),
+
+        Some(Commands::ExportGit { output, git_ref }) => export_git(output, git_ref),
+
+        Some(Commands::CatObject { hash, show_type, size, pretty }) => cat_object(hash, *show_type, *size, *pretty),
+
+        Some(Commands::LsTree { object, path, recursive }) => ls_tree(object, path, *recursive),
+
+        Some(Commands::HashObject { file, write }) => hash_object(file, *write),
+
+        Some(Commands::CheckIgnore { paths }) => check_ignore(paths),
+
         None => info()
     }
 }
@@ -0,0 +1,325 @@
+use crate::bucket::{check_tree_hash, get_ancestors, Commit, ObjectType, TreeEntry};
+use crate::remote::{local::LocalTransport, transport::{check_hash, Transport}};
+use crate::util::{get_data_hash, try_decompress_data};
+use std::{
+    collections::{HashMap, HashSet},
+    fs::{self, File},
+    io::{BufRead, BufReader, Error},
+    path::{Path, PathBuf},
+};
+
+const BUNDLE_HEADER: &str = "svc bundle 1";
+
+/// A repository packed into a single file, it can only be read from.
+///
+/// The file starts with `svc bundle 1`, then `requires <hash>` for every
+/// commit the receiver must already have, `head <hash>`, the log and the
+/// metadata as `log <len>`/`meta <len>` followed by their bytes, and every
+/// object as `object <hash> <blob|tree> <len>` followed by its data as stored.
+/// It ends with `checksum <sha1>` of everything before.
+pub struct BundleTransport {
+    path: PathBuf,
+    pub requires: Vec<String>,
+    head: String,
+    log: String,
+    meta: String,
+    objects: HashMap<String, Vec<u8>>,
+}
+
+impl BundleTransport {
+    pub fn is_bundle(path: &Path) -> bool {
+        let mut line = String::new();
+        match File::open(path) {
+            Ok(file) => BufReader::new(file).read_line(&mut line).is_ok() && line.trim_end() == BUNDLE_HEADER,
+            Err(_) => false,
+        }
+    }
+
+    /// Read a bundle, checking its checksum and the hash of every object.
+    pub fn open(path: PathBuf) -> Result<BundleTransport, Error> {
+        let data = fs::read(&path)?;
+        BundleTransport::parse(path, &data)
+    }
+
+    fn parse(path: PathBuf, data: &[u8]) -> Result<BundleTransport, Error> {
+        let invalid = |reason: &str| Error::other(format!("'{}' is not a valid bundle: {}", path.display(), reason));
+        let mut bundle = BundleTransport {
+            path: path.clone(),
+            requires: Vec::new(),
+            head: String::new(),
+            log: String::new(),
+            meta: String::new(),
+            objects: HashMap::new(),
+        };
+        let mut pos = 0;
+        let mut first = true;
+        loop {
+            let line_end = match data[pos..].iter().position(|byte| *byte == b'\n') {
+                Some(len) => pos + len,
+                None => return Err(invalid("truncated")),
+            };
+            let line = String::from_utf8_lossy(&data[pos..line_end]).to_string();
+            let words: Vec<&str> = line.split(' ').collect();
+            if first {
+                if line != BUNDLE_HEADER {
+                    return Err(invalid("unknown header"));
+                }
+                first = false;
+                pos = line_end + 1;
+                continue;
+            }
+            match words[..] {
+                ["requires", hash] => bundle.requires.push(hash.to_string()),
+                ["head", hash] => bundle.head = hash.to_string(),
+                ["checksum", checksum] => {
+                    if get_data_hash(&data[..pos]) != checksum {
+                        return Err(invalid("checksum mismatch"));
+                    }
+                    return Ok(bundle);
+                }
+                ["log", len] | ["meta", len] | ["object", _, _, len] => {
+                    let len: usize = len.parse().map_err(|_| invalid("bad length"))?;
+                    let start = line_end + 1;
+                    if len > data.len() - start {
+                        return Err(invalid("truncated"));
+                    }
+                    let body = data[start..start + len].to_vec();
+                    match words[..] {
+                        ["log", _] => bundle.log = String::from_utf8_lossy(&body).to_string(),
+                        ["meta", _] => bundle.meta = String::from_utf8_lossy(&body).to_string(),
+                        ["object", hash, object_type, _] => {
+                            check_hash(hash)?;
+                            let valid = match object_type {
+                                "tree" => check_tree_hash(hash, &String::from_utf8_lossy(&body)),
+                                _ => try_decompress_data(&body).is_ok_and(|data| get_data_hash(&data) == hash),
+                            };
+                            if !valid {
+                                return Err(invalid(&format!("object {} is corrupted", hash)));
+                            }
+                            bundle.objects.insert(hash.to_string(), body);
+                        }
+                        _ => unreachable!(),
+                    }
+                    pos = start + len;
+                    continue;
+                }
+                _ => return Err(invalid(&format!("unexpected line '{}'", line))),
+            }
+            pos = line_end + 1;
+        }
+    }
+
+    fn read_only(&self) -> Error {
+        Error::other(format!("'{}' is a bundle and can not be pushed to", self.path.display()))
+    }
+}
+
+impl Transport for BundleTransport {
+    fn read_log(&mut self) -> Result<String, Error> {
+        Ok(self.log.clone())
+    }
+
+    fn read_head(&mut self) -> Result<String, Error> {
+        Ok(self.head.clone())
+    }
+
+    fn read_meta(&mut self) -> Result<String, Error> {
+        Ok(self.meta.clone())
+    }
+
+    fn has_objects(&mut self, hashes: &[String]) -> Result<Vec<bool>, Error> {
+        Ok(hashes.iter().map(|hash| self.objects.contains_key(hash)).collect())
+    }
+
+    fn read_object(&mut self, hash: &str) -> Result<Vec<u8>, Error> {
+        match self.objects.get(hash) {
+            Some(data) => Ok(data.clone()),
+            None => Err(Error::new(
+                std::io::ErrorKind::NotFound,
+                format!("object {} is not in the bundle, it requires commits {}", hash, self.requires.join(", ")),
+            )),
+        }
+    }
+
+    fn write_object(&mut self, _hash: &str, _data: &[u8]) -> Result<(), Error> {
+        Err(self.read_only())
+    }
+
+    fn update_log(&mut self, _old_latest: &str, _log: &str) -> Result<(), Error> {
+        Err(self.read_only())
+    }
+}
+
+/// Pack the commits of `range` into a bundle at `path`. The range is a
+/// version, everything up to it, or `<from>..[<to>]`, the commits after `from`
+/// the receiver already has. Without a range the whole log is packed.
+pub fn create_bundle(svc_path: PathBuf, path: &Path, range: &Option<String>) -> Result<usize, Error> {
+    let commits = Commit::read_from_log(svc_path.clone());
+    let find = |version: &str| Commit::find_logged_commit(svc_path.clone(), version).map_err(Error::other);
+    let (from, to) = match range {
+        None => match commits.last() {
+            Some(commit) => (None, commit.clone()),
+            None => return Err(Error::other("no commit yet")),
+        },
+        Some(range) => match range.split_once("..") {
+            None => (None, find(range)?),
+            Some((from, to)) => {
+                let to = if to.is_empty() { "HEAD" } else { to };
+                (Some(find(from)?), find(to)?)
+            }
+        },
+    };
+
+    let included = get_ancestors(&commits, &to.hash);
+    let known = match &from {
+        Some(from) => get_ancestors(&commits, &from.hash),
+        None => Vec::new(),
+    };
+    let mut local = LocalTransport::new(svc_path.clone());
+    let mut skip = HashSet::new();
+    for commit in commits.iter().filter(|commit| known.contains(&commit.hash)) {
+        collect_objects(&mut local, &commit.tree_hash, &mut skip, &mut Vec::new())?;
+    }
+    let mut objects = Vec::new();
+    let mut packed = 0;
+    for commit in commits.iter().filter(|commit| included.contains(&commit.hash) && !known.contains(&commit.hash)) {
+        collect_objects(&mut local, &commit.tree_hash, &mut skip, &mut objects)?;
+        packed += 1;
+    }
+    if packed == 0 {
+        return Err(Error::other("refusing to create an empty bundle"));
+    }
+
+    let mut data = format!("{}\n", BUNDLE_HEADER).into_bytes();
+    if let Some(from) = &from {
+        data.extend(format!("requires {}\n", from.hash).as_bytes());
+    }
+    data.extend(format!("head {}\n", to.hash).as_bytes());
+    let mut log = String::new();
+    for commit in commits.iter().filter(|commit| included.contains(&commit.hash)) {
+        log += &commit.to_log_line();
+    }
+    data.extend(format!("log {}\n", log.len()).as_bytes());
+    data.extend(log.as_bytes());
+    let meta = local.read_meta()?;
+    data.extend(format!("meta {}\n", meta.len()).as_bytes());
+    data.extend(meta.as_bytes());
+    for (hash, object_type) in objects {
+        let object = local.read_object(&hash)?;
+        data.extend(format!("object {} {} {}\n", hash, object_type, object.len()).as_bytes());
+        data.extend(object);
+    }
+    let checksum = get_data_hash(&data);
+    data.extend(format!("checksum {}\n", checksum).as_bytes());
+    fs::write(path, data)?;
+    Ok(packed)
+}
+
+// every object of a tree not seen yet, as (hash, "blob" or "tree")
+fn collect_objects(
+    local: &mut LocalTransport,
+    tree_hash: &str,
+    seen: &mut HashSet<String>,
+    objects: &mut Vec<(String, &'static str)>,
+) -> Result<(), Error> {
+    if !seen.insert(tree_hash.to_string()) {
+        return Ok(());
+    }
+    let data = local.read_object(tree_hash)?;
+    for entry in TreeEntry::parse_tree(&String::from_utf8_lossy(&data))? {
+        match entry.object_type {
+            ObjectType::ObjectTree => collect_objects(local, &entry.hash, seen, objects)?,
+            ObjectType::ObjectBlob | ObjectType::ObjectLink => {
+                if seen.insert(entry.hash.clone()) {
+                    objects.push((entry.hash, "blob"));
+                }
+            }
+        }
+    }
+    objects.push((tree_hash.to_string(), "tree"));
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::util::compress_data;
+
+    // a bundle of the given sections, with its checksum
+    fn bundle_data(sections: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut data = format!("{}\n", BUNDLE_HEADER).into_bytes();
+        for (line, body) in sections {
+            data.extend(format!("{}\n", line).as_bytes());
+            data.extend(*body);
+        }
+        let checksum = get_data_hash(&data);
+        data.extend(format!("checksum {}\n", checksum).as_bytes());
+        data
+    }
+
+    fn parse(data: &[u8]) -> Result<BundleTransport, Error> {
+        BundleTransport::parse(PathBuf::from("test.bundle"), data)
+    }
+
+    #[test]
+    fn parse_valid_bundle() {
+        let blob = compress_data(b"hello\n");
+        let hash = get_data_hash(b"hello\n");
+        let data = bundle_data(&[
+            (&format!("requires {}", "1".repeat(40)), b""),
+            (&format!("head {}", "2".repeat(40)), b""),
+            ("log 4", b"log\n"),
+            ("meta 2", b"m\n"),
+            (&format!("object {} blob {}", hash, blob.len()), &blob),
+        ]);
+        let mut bundle = parse(&data).unwrap();
+        assert_eq!(bundle.requires, vec!["1".repeat(40)]);
+        assert_eq!(bundle.read_head().unwrap(), "2".repeat(40));
+        assert_eq!(bundle.read_log().unwrap(), "log\n");
+        assert_eq!(bundle.read_meta().unwrap(), "m\n");
+        assert_eq!(bundle.read_object(&hash).unwrap(), blob);
+        assert!(bundle.read_object(&"3".repeat(40)).is_err());
+    }
+
+    #[test]
+    fn reject_checksum_mismatch() {
+        let mut data = bundle_data(&[("log 4", b"log\n")]);
+        let index = BUNDLE_HEADER.len() + 7;
+        data[index] = b'x';
+        let err = parse(&data).err().unwrap();
+        assert!(err.to_string().contains("checksum mismatch"));
+    }
+
+    #[test]
+    fn reject_corrupted_object() {
+        let hash = get_data_hash(b"hello\n");
+        // not zlib data at all
+        let data = bundle_data(&[(&format!("object {} blob 4", hash), b"junk")]);
+        let err = parse(&data).err().unwrap();
+        assert!(err.to_string().contains("is corrupted"));
+
+        // zlib data of something else
+        let blob = compress_data(b"other\n");
+        let data = bundle_data(&[(&format!("object {} blob {}", hash, blob.len()), &blob)]);
+        assert!(parse(&data).is_err());
+
+        let data = bundle_data(&[(&format!("object {} tree 4", hash), b"junk")]);
+        assert!(parse(&data).is_err());
+    }
+
+    #[test]
+    fn reject_truncated_bundle() {
+        let data = bundle_data(&[("log 4", b"log\n")]);
+        assert!(parse(&data[..data.len() - 1]).is_err());
+        assert!(parse(b"svc bundle 1\nlog 100\nlog\n").is_err());
+        assert!(parse(format!("svc bundle 1\nlog {}\nlog\n", usize::MAX).as_bytes()).is_err());
+        assert!(parse(b"svc bundle 1\nlog x\nlog\n").is_err());
+    }
+
+    #[test]
+    fn reject_unknown_lines() {
+        assert!(parse(b"svc bundle 2\n").is_err());
+        assert!(parse(&bundle_data(&[("unknown", b"")])).is_err());
+        assert!(parse(b"").is_err());
+    }
+}
@@ -0,0 +1,247 @@
+use crate::remote::{
+    local::LocalTransport,
+    service::{
+        decode_has, encode_hashes, encode_update, handle_request, CMD_HAS, CMD_HEAD, CMD_LOG,
+        CMD_META, CMD_READ, CMD_UPDATE, CMD_WRITE,
+    },
+    transport::Transport,
+};
+use std::{
+    env,
+    io::{self, BufRead, BufReader, Error, ErrorKind, Read, Write},
+    path::PathBuf,
+    process::{Child, ChildStdin, ChildStdout, Command, Stdio},
+};
+
+// Requests are framed as a "<command> <arg> <length>" line followed by the
+// body, responses as "ok <length>", "missing <length>" or "err <length>"
+// followed by the body or the error message.
+
+/// A repository served by a `svc serve --stdio` process we spawned.
+pub struct CommandTransport {
+    child: Child,
+    stdin: Option<ChildStdin>,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl CommandTransport {
+    /// `ssh://[user@]host[:port]/path`, running `svc` on the remote host.
+    /// `SVC_SSH` replaces the ssh program.
+    pub fn ssh(url: &str) -> Result<CommandTransport, Error> {
+        let (host, port, path) = parse_ssh_url(url)?;
+        let ssh = env::var("SVC_SSH").unwrap_or_else(|_| String::from("ssh"));
+        let mut command = Command::new(ssh);
+        if let Some(port) = port {
+            command.arg("-p").arg(port);
+        }
+        // ssh joins its arguments into one line for the remote shell
+        command.arg(host).arg(format!("svc serve --stdio {}", shell_quote(&path)));
+        CommandTransport::spawn(command)
+    }
+
+    /// `ext::<command>`, running the command through the shell.
+    pub fn ext(url: &str) -> Result<CommandTransport, Error> {
+        let mut command = Command::new("sh");
+        command.arg("-c").arg(url.trim_start_matches("ext::"));
+        CommandTransport::spawn(command)
+    }
+
+    fn spawn(mut command: Command) -> Result<CommandTransport, Error> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::inherit())
+            .spawn()?;
+        let stdin = child.stdin.take();
+        let stdout = BufReader::new(child.stdout.take().unwrap());
+        Ok(CommandTransport { child, stdin, stdout })
+    }
+
+    fn request(&mut self, command: &str, arg: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let stdin = self.stdin.as_mut().unwrap();
+        let arg = if arg.is_empty() { "-" } else { arg };
+        stdin.write_fmt(format_args!("{} {} {}\n", command, arg, body.len()))?;
+        stdin.write_all(body)?;
+        stdin.flush()?;
+
+        let mut header = String::new();
+        if self.stdout.read_line(&mut header)? == 0 {
+            return Err(Error::other("remote helper exited unexpectedly"));
+        }
+        let (status, len) = parse_header(&header)?;
+        let data = read_body(&mut self.stdout, len)?;
+        match status.as_str() {
+            "ok" => Ok(data),
+            "missing" => Err(Error::new(ErrorKind::NotFound, format!("remote: {}", String::from_utf8_lossy(&data)))),
+            _ => Err(Error::other(format!("remote: {}", String::from_utf8_lossy(&data)))),
+        }
+    }
+}
+
+// split `ssh://[user@]host[:port]/path` into host, port and path, the host
+// may be an IPv6 address in brackets
+fn parse_ssh_url(url: &str) -> Result<(String, Option<String>, String), Error> {
+    let rest = url.trim_start_matches("ssh://");
+    let (authority, path) = match rest.find('/') {
+        Some(index) => (&rest[..index], &rest[index..]),
+        None => return Err(Error::other(format!("missing repo path in '{}'", url))),
+    };
+    let (user, host_port) = match authority.rsplit_once('@') {
+        Some((user, host_port)) => (format!("{}@", user), host_port),
+        None => (String::new(), authority),
+    };
+    let (host, port) = match host_port.strip_prefix('[') {
+        Some(bracketed) => match bracketed.split_once(']') {
+            Some((host, "")) => (host, None),
+            Some((host, port)) => match port.strip_prefix(':') {
+                Some(port) => (host, Some(port)),
+                None => return Err(Error::other(format!("invalid host in '{}'", url))),
+            },
+            None => return Err(Error::other(format!("invalid host in '{}'", url))),
+        },
+        None => match host_port.split_once(':') {
+            Some((host, port)) => (host, Some(port)),
+            None => (host_port, None),
+        },
+    };
+    if port.is_some_and(|port| port.is_empty() || !port.chars().all(|c| c.is_ascii_digit())) {
+        return Err(Error::other(format!("invalid port in '{}'", url)));
+    }
+    // a host like "-oProxyCommand=..." would be taken as an option
+    if host.is_empty() || user.starts_with('-') || (user.is_empty() && host.starts_with('-')) {
+        return Err(Error::other(format!("invalid host in '{}'", url)));
+    }
+    Ok((format!("{}{}", user, host), port.map(|port| port.to_string()), path.to_string()))
+}
+
+// quote a word for a POSIX shell
+fn shell_quote(word: &str) -> String {
+    format!("'{}'", word.replace('\'', "'\\''"))
+}
+
+impl Drop for CommandTransport {
+    fn drop(&mut self) {
+        // closing stdin tells the helper we are done
+        self.stdin.take();
+        let _ = self.child.wait();
+    }
+}
+
+impl Transport for CommandTransport {
+    fn read_log(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request(CMD_LOG, "", &[])?).to_string())
+    }
+
+    fn read_head(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request(CMD_HEAD, "", &[])?).to_string())
+    }
+
+    fn read_meta(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request(CMD_META, "", &[])?).to_string())
+    }
+
+    fn has_objects(&mut self, hashes: &[String]) -> Result<Vec<bool>, Error> {
+        let body = self.request(CMD_HAS, "", encode_hashes(hashes).as_bytes())?;
+        decode_has(&body, hashes.len())
+    }
+
+    fn read_object(&mut self, hash: &str) -> Result<Vec<u8>, Error> {
+        self.request(CMD_READ, hash, &[])
+    }
+
+    fn write_object(&mut self, hash: &str, data: &[u8]) -> Result<(), Error> {
+        self.request(CMD_WRITE, hash, data)?;
+        Ok(())
+    }
+
+    fn update_log(&mut self, old_latest: &str, log: &str) -> Result<(), Error> {
+        self.request(CMD_UPDATE, "", encode_update(old_latest, log).as_bytes())?;
+        Ok(())
+    }
+}
+
+fn parse_header(header: &str) -> Result<(String, usize), Error> {
+    let fields: Vec<&str> = header.split_whitespace().collect();
+    if fields.is_empty() {
+        return Err(Error::other("malformed message"));
+    }
+    match fields[fields.len() - 1].parse::<usize>() {
+        Ok(len) => Ok((fields[0..fields.len() - 1].join(" "), len)),
+        Err(_) => Err(Error::other("malformed message")),
+    }
+}
+
+fn read_body(reader: &mut impl Read, len: usize) -> Result<Vec<u8>, Error> {
+    let mut body = vec![0; len];
+    reader.read_exact(&mut body)?;
+    Ok(body)
+}
+
+pub fn serve_stdio(svc_path: PathBuf) -> Result<(), Error> {
+    let mut repo = LocalTransport::new(svc_path);
+    let mut stdin = BufReader::new(io::stdin().lock());
+    let mut stdout = io::stdout().lock();
+    loop {
+        let mut header = String::new();
+        if stdin.read_line(&mut header)? == 0 {
+            return Ok(());
+        }
+        let (request, len) = parse_header(&header)?;
+        let body = read_body(&mut stdin, len)?;
+        let (command, arg) = match request.split_once(' ') {
+            Some((command, "-")) => (command.to_string(), String::new()),
+            Some((command, arg)) => (command.to_string(), arg.to_string()),
+            None => (request, String::new()),
+        };
+        let (status, data) = match handle_request(&mut repo, &command, &arg, &body) {
+            Ok(data) => ("ok", data),
+            Err(err) if err.kind() == ErrorKind::NotFound => ("missing", err.to_string().into_bytes()),
+            Err(err) => ("err", err.to_string().into_bytes()),
+        };
+        stdout.write_fmt(format_args!("{} {}\n", status, data.len()))?;
+        stdout.write_all(&data)?;
+        stdout.flush()?;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(url: &str) -> Option<(String, Option<String>, String)> {
+        parse_ssh_url(url).ok()
+    }
+
+    fn parsed(host: &str, port: Option<&str>, path: &str) -> Option<(String, Option<String>, String)> {
+        Some((host.to_string(), port.map(|port| port.to_string()), path.to_string()))
+    }
+
+    #[test]
+    fn parse_ssh_urls() {
+        assert_eq!(parse("ssh://host/srv/repo"), parsed("host", None, "/srv/repo"));
+        assert_eq!(parse("ssh://me@host:2222/srv/repo"), parsed("me@host", Some("2222"), "/srv/repo"));
+        assert_eq!(parse("ssh://[::1]/repo"), parsed("::1", None, "/repo"));
+        assert_eq!(parse("ssh://me@[fe80::1%eth0]:22/repo"), parsed("me@fe80::1%eth0", Some("22"), "/repo"));
+        assert_eq!(parse("ssh://host/path with 'quotes'"), parsed("host", None, "/path with 'quotes'"));
+    }
+
+    #[test]
+    fn reject_bad_ssh_urls() {
+        assert!(parse("ssh://host").is_none());
+        assert!(parse("ssh:///repo").is_none());
+        assert!(parse("ssh://host:/repo").is_none());
+        assert!(parse("ssh://host:22x/repo").is_none());
+        assert!(parse("ssh://[::1/repo").is_none());
+        assert!(parse("ssh://[::1]x/repo").is_none());
+        assert!(parse("ssh://-oProxyCommand=evil/repo").is_none());
+        assert!(parse("ssh://-me@host/repo").is_none());
+    }
+
+    #[test]
+    fn quote_for_the_shell() {
+        assert_eq!(shell_quote("/srv/repo"), "'/srv/repo'");
+        assert_eq!(shell_quote("a b;$(rm -rf x)"), "'a b;$(rm -rf x)'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
@@ -0,0 +1,118 @@
+use crate::remote::{
+    local::LocalTransport,
+    service::{
+        decode_has, encode_hashes, encode_update, handle_request, CMD_HAS, CMD_HEAD, CMD_LOG,
+        CMD_META, CMD_READ, CMD_UPDATE, CMD_WRITE,
+    },
+    transport::Transport,
+};
+use std::{
+    io::{Error, ErrorKind, Read},
+    path::PathBuf,
+};
+use tiny_http::{Method, Response, Server};
+
+/// A repository served by `svc serve`.
+pub struct HttpTransport {
+    url: String,
+}
+
+impl HttpTransport {
+    pub fn new(url: &str) -> HttpTransport {
+        HttpTransport {
+            url: url.trim_end_matches('/').to_string(),
+        }
+    }
+
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+        let url = format!("{}/{}", self.url, path);
+        let response = match ureq::request(method, &url).send_bytes(body) {
+            Ok(response) => response,
+            Err(ureq::Error::Status(code, response)) => {
+                let message = response.into_string().unwrap_or_default();
+                let kind = if code == 404 { ErrorKind::NotFound } else { ErrorKind::Other };
+                return Err(Error::new(kind, format!("remote: {}", message.trim())));
+            }
+            Err(err) => return Err(Error::other(err.to_string())),
+        };
+        let mut data = Vec::new();
+        response.into_reader().read_to_end(&mut data)?;
+        Ok(data)
+    }
+}
+
+impl Transport for HttpTransport {
+    fn read_log(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request("GET", CMD_LOG, &[])?).to_string())
+    }
+
+    fn read_head(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request("GET", CMD_HEAD, &[])?).to_string())
+    }
+
+    fn read_meta(&mut self) -> Result<String, Error> {
+        Ok(String::from_utf8_lossy(&self.request("GET", CMD_META, &[])?).to_string())
+    }
+
+    fn has_objects(&mut self, hashes: &[String]) -> Result<Vec<bool>, Error> {
+        let body = self.request("POST", CMD_HAS, encode_hashes(hashes).as_bytes())?;
+        decode_has(&body, hashes.len())
+    }
+
+    fn read_object(&mut self, hash: &str) -> Result<Vec<u8>, Error> {
+        self.request("GET", &format!("objects/{}", hash), &[])
+    }
+
+    fn write_object(&mut self, hash: &str, data: &[u8]) -> Result<(), Error> {
+        self.request("PUT", &format!("objects/{}", hash), data)?;
+        Ok(())
+    }
+
+    fn update_log(&mut self, old_latest: &str, log: &str) -> Result<(), Error> {
+        self.request("POST", CMD_UPDATE, encode_update(old_latest, log).as_bytes())?;
+        Ok(())
+    }
+}
+
+// map a route to a request of the sync protocol
+fn route(method: &Method, path: &str) -> Option<(&'static str, String)> {
+    let path = path.trim_start_matches('/');
+    match (method, path.split_once('/')) {
+        (Method::Get, None) if path == CMD_LOG => Some((CMD_LOG, String::new())),
+        (Method::Get, None) if path == CMD_HEAD => Some((CMD_HEAD, String::new())),
+        (Method::Get, None) if path == CMD_META => Some((CMD_META, String::new())),
+        (Method::Post, None) if path == CMD_HAS => Some((CMD_HAS, String::new())),
+        (Method::Post, None) if path == CMD_UPDATE => Some((CMD_UPDATE, String::new())),
+        (Method::Get, Some(("objects", hash))) => Some((CMD_READ, hash.to_string())),
+        (Method::Put, Some(("objects", hash))) => Some((CMD_WRITE, hash.to_string())),
+        _ => None,
+    }
+}
+
+pub fn serve_http(svc_path: PathBuf, bind: &str) -> Result<(), Error> {
+    let server = Server::http(bind).map_err(|err| Error::other(err.to_string()))?;
+    let mut repo = LocalTransport::new(svc_path.clone());
+    println!("serving {} on http://{}", svc_path.parent().unwrap().to_str().unwrap(), bind);
+    for mut request in server.incoming_requests() {
+        let mut body = Vec::new();
+        let result = match request.as_reader().read_to_end(&mut body) {
+            Ok(_) => match route(request.method(), request.url()) {
+                Some((command, arg)) => handle_request(&mut repo, command, &arg, &body),
+                None => Err(Error::new(ErrorKind::NotFound, "no such route")),
+            },
+            Err(err) => Err(err),
+        };
+        let response = match result {
+            Ok(data) => Response::from_data(data),
+            Err(err) => {
+                let code = if err.kind() == ErrorKind::NotFound { 404 } else { 400 };
+                Response::from_string(err.to_string()).with_status_code(code)
+            }
+        };
+        println!("{} {} {}", request.method(), request.url(), response.status_code().0);
+        if let Err(err) = request.respond(response) {
+            eprintln!("error: {}", err);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,132 @@
+use crate::bucket::{check_workspace, get_tree_of_commit, Commit};
+use crate::remote::transport::{check_hash, Transport};
+use std::{
+    fs::{self, File},
+    io::{Error, Read, Write},
+    path::PathBuf,
+};
+
+/// A repository reachable through the filesystem.
+pub struct LocalTransport {
+    pub svc_path: PathBuf,
+}
+
+impl LocalTransport {
+    pub fn new(svc_path: PathBuf) -> LocalTransport {
+        LocalTransport { svc_path }
+    }
+
+    /// Open a workspace directory containing `.svc`, or a `.svc` directory itself.
+    pub fn open(path: PathBuf) -> Result<LocalTransport, Error> {
+        if path.join(".svc").join("log").is_file() {
+            return Ok(LocalTransport::new(path.join(".svc")));
+        }
+        if path.join("log").is_file() && path.join("objects").is_dir() {
+            return Ok(LocalTransport::new(path));
+        }
+        Err(Error::other(format!(
+            "'{}' is not a svc repo",
+            path.to_str().unwrap()
+        )))
+    }
+
+    fn object_path(&self, hash: &str) -> Result<PathBuf, Error> {
+        check_hash(hash)?;
+        Ok(self.svc_path.join("objects").join(&hash[0..2]).join(&hash[2..]))
+    }
+
+    /// Replace the log, as long as its latest commit is still `old_latest`,
+    /// leaving HEAD and the workspace alone.
+    pub fn write_log(&mut self, old_latest: &str, log: &str) -> Result<(), Error> {
+        self.check_latest(old_latest)?;
+        let new_latest = match Commit::parse_log(log).last() {
+            Some(commit) => commit.hash.clone(),
+            None => String::new(),
+        };
+        self.write_file("log", log.as_bytes())?;
+        self.write_file("latest", new_latest.as_bytes())
+    }
+
+    fn check_latest(&mut self, old_latest: &str) -> Result<(), Error> {
+        let latest = match Commit::parse_log(&self.read_log()?).last() {
+            Some(commit) => commit.hash.clone(),
+            None => String::new(),
+        };
+        if latest != old_latest {
+            return Err(Error::other("repo was updated concurrently, try again"));
+        }
+        Ok(())
+    }
+
+    fn write_file(&self, name: &str, data: &[u8]) -> Result<(), Error> {
+        let tmp_path = self.svc_path.join(format!("{}.tmp", name));
+        File::create(&tmp_path)?.write_all(data)?;
+        fs::rename(tmp_path, self.svc_path.join(name))
+    }
+}
+
+impl Transport for LocalTransport {
+    fn read_log(&mut self) -> Result<String, Error> {
+        let mut log = String::new();
+        File::open(self.svc_path.join("log"))?.read_to_string(&mut log)?;
+        Ok(log)
+    }
+
+    fn read_head(&mut self) -> Result<String, Error> {
+        Ok(Commit::get_head_hash(self.svc_path.clone()))
+    }
+
+    fn read_meta(&mut self) -> Result<String, Error> {
+        let mut meta = String::new();
+        File::open(self.svc_path.join("repo"))?.read_to_string(&mut meta)?;
+        Ok(meta)
+    }
+
+    fn has_objects(&mut self, hashes: &[String]) -> Result<Vec<bool>, Error> {
+        let mut has = Vec::new();
+        for hash in hashes {
+            has.push(self.object_path(hash)?.is_file());
+        }
+        Ok(has)
+    }
+
+    fn read_object(&mut self, hash: &str) -> Result<Vec<u8>, Error> {
+        let mut data = Vec::new();
+        File::open(self.object_path(hash)?)?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+
+    fn write_object(&mut self, hash: &str, data: &[u8]) -> Result<(), Error> {
+        let object_path = self.object_path(hash)?;
+        fs::create_dir_all(object_path.parent().unwrap())?;
+        let tmp_path = object_path.with_extension("tmp");
+        File::create(&tmp_path)?.write_all(data)?;
+        fs::rename(tmp_path, object_path)
+    }
+
+    fn update_log(&mut self, old_latest: &str, log: &str) -> Result<(), Error> {
+        self.check_latest(old_latest)?;
+        let (new_latest, new_tree) = match Commit::parse_log(log).last() {
+            Some(commit) => (commit.hash.clone(), commit.tree_hash.clone()),
+            None => return self.write_log(old_latest, log),
+        };
+        // a workspace on the latest commit moves along with it, unless that
+        // would lose changes made in it
+        let head = Commit::get_head_hash(self.svc_path.clone());
+        if !head.is_empty() && head != old_latest {
+            return self.write_log(old_latest, log);
+        }
+        let head_tree = get_tree_of_commit(self.svc_path.clone(), head).ok();
+        check_workspace(self.svc_path.clone(), head_tree.as_deref(), &new_tree, "push")
+            .map_err(|err| Error::other(format!("remote workspace can't be updated: {}", err)))?;
+        self.write_log(old_latest, log)?;
+
+        let root_path = self.svc_path.parent().unwrap().to_path_buf();
+        Commit::restore_tree(root_path, self.svc_path.clone(), new_tree.clone())?;
+        if let Some(head_tree) = head_tree {
+            Commit::remove_stale_files(self.svc_path.clone(), &head_tree, &new_tree)?;
+            Commit::remove_stale_dirs(self.svc_path.clone(), head_tree, new_tree)?;
+        }
+        self.write_file("head", new_latest.as_bytes())
+    }
+}
@@ -1,12 +1,333 @@
-pub fn pull() {
-    println!("Command pull")
+mod bundle;
+mod command;
+mod http;
+mod local;
+mod service;
+mod sync;
+mod transport;
+
+use crate::bucket::{check_svc_repo, create_svc_repo, RepoMeta, DEFAULT_REMOTE};
+use std::{env, fs, io::Error, path::PathBuf, process};
+use bundle::BundleTransport;
+use local::LocalTransport;
+use transport::{open_transport, Transport};
+
+pub fn pull(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = open_remote(svc_path.clone(), name).and_then(|(name, mut remote)| sync::pull(&mut *remote, svc_path, &name)) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
 }
 
-pub fn push() {
-    println!("Command push")
+pub fn push(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = open_remote(svc_path.clone(), name).and_then(|(name, mut remote)| sync::push(&mut *remote, svc_path, &name)) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
 }
 
-pub fn set_remote(url: &String) {
-    println!("Command set-remote");
-    println!("{}", url)
-}
\ No newline at end of file
+pub fn fetch(name: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            if let Err(err) = open_remote(svc_path.clone(), name).and_then(|(name, mut remote)| sync::fetch(&mut *remote, svc_path, &name)) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn clone(url: &str, dir: &Option<String>) {
+    let current_dir = env::current_dir().unwrap();
+    // plain paths are kept absolute, the clone lives somewhere else
+    let url = if url.contains("://") || url.starts_with("ext::") {
+        url.to_string()
+    } else {
+        current_dir.join(url).to_str().unwrap().to_string()
+    };
+    let dir = match dir {
+        Some(dir) => current_dir.join(dir),
+        None => match default_clone_dir(&url) {
+            Some(name) => current_dir.join(name),
+            None => {
+                eprintln!("error: can not guess a directory name from '{}', please give one", url);
+                process::exit(1)
+            }
+        },
+    };
+    if let Ok(mut entries) = fs::read_dir(&dir) {
+        if entries.next().is_some() {
+            eprintln!("error: '{}' already exists and is not empty", dir.to_str().unwrap());
+            process::exit(1);
+        }
+    }
+    let mut remote = match open_transport(&url, current_dir) {
+        Ok(remote) => remote,
+        Err(err) => {
+            eprintln!("error: {}", err);
+            process::exit(1)
+        }
+    };
+    let existed = dir.exists();
+    let result = fs::create_dir_all(&dir)
+        .and_then(|_| create_svc_repo(dir.clone()))
+        .and_then(|svc_path| sync::clone(&mut *remote, svc_path, &url));
+    if let Err(err) = result {
+        // leave nothing half cloned behind
+        if existed {
+            let _ = fs::remove_dir_all(dir.join(".svc"));
+        } else {
+            let _ = fs::remove_dir_all(&dir);
+        }
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+// the last path component of the url, without a trailing ".svc"
+fn default_clone_dir(url: &str) -> Option<String> {
+    if url.starts_with("ext::") {
+        return None;
+    }
+    let path = match url.split_once("://") {
+        Some((_, rest)) => rest.split_once('/').map(|(_, path)| path).unwrap_or(""),
+        None => url,
+    };
+    let name = path
+        .trim_end_matches('/')
+        .trim_end_matches(".bundle")
+        .trim_end_matches(".svc")
+        .trim_end_matches('/')
+        .rsplit('/')
+        .next()?;
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    Some(name.to_string())
+}
+
+pub fn bundle_create(file: &str, range: &Option<String>) {
+    match check_svc_repo() {
+        Ok(svc_path) => match bundle::create_bundle(svc_path, &PathBuf::from(file), range) {
+            Ok(packed) => println!("bundle {} commit(s) into '{}'.", packed, file),
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        },
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn bundle_unbundle(file: &str) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let result = BundleTransport::open(PathBuf::from(file))
+                .and_then(|mut bundle| sync::unbundle(&mut bundle, svc_path, file));
+            if let Err(err) = result {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+// set the url of the default remote
+pub fn set_remote(url: &str) {
+    update_remotes(|meta, _| {
+        let name = meta.default_remote.clone();
+        meta.set_remote(name.clone(), url.to_string());
+        println!("remote.{} = {}", name, url);
+        Ok(())
+    })
+}
+
+pub fn remote_list() {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let meta = RepoMeta::read_repo_meta(svc_path);
+            for remote in &meta.remotes {
+                if remote.name == meta.default_remote {
+                    println!("{}\t{} (default)", remote.name, remote.url);
+                } else {
+                    println!("{}\t{}", remote.name, remote.url);
+                }
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+pub fn remote_add(name: &str, url: &str) {
+    update_remotes(|meta, _| {
+        check_remote_name(name)?;
+        if meta.get_remote(name).is_some() {
+            return Err(Error::other(format!("remote '{}' already exists", name)));
+        }
+        meta.set_remote(name.to_string(), url.to_string());
+        println!("remote '{}' added.", name);
+        Ok(())
+    })
+}
+
+pub fn remote_remove(name: &str) {
+    update_remotes(|meta, svc_path| {
+        if meta.get_remote(name).is_none() {
+            return Err(Error::other(format!("no such remote '{}'", name)));
+        }
+        meta.remotes.retain(|remote| remote.name != name);
+        let _ = fs::remove_dir_all(svc_path.join("remotes").join(name));
+        println!("remote '{}' removed.", name);
+        // the default falls back to another remote, or to the name a new one would get
+        if meta.default_remote == name {
+            meta.default_remote = match meta.remotes.first() {
+                Some(remote) => remote.name.clone(),
+                None => DEFAULT_REMOTE.to_string(),
+            };
+            if let Some(remote) = meta.get_remote(&meta.default_remote) {
+                println!("default remote is now '{}'.", remote.name);
+            }
+        }
+        Ok(())
+    })
+}
+
+pub fn remote_rename(old_name: &str, new_name: &str) {
+    update_remotes(|meta, svc_path| {
+        check_remote_name(new_name)?;
+        if meta.get_remote(new_name).is_some() {
+            return Err(Error::other(format!("remote '{}' already exists", new_name)));
+        }
+        match meta.remotes.iter_mut().find(|remote| remote.name == old_name) {
+            Some(remote) => remote.name = new_name.to_string(),
+            None => return Err(Error::other(format!("no such remote '{}'", old_name))),
+        }
+        if meta.default_remote == old_name {
+            meta.default_remote = new_name.to_string();
+        }
+        let remotes_dir = svc_path.join("remotes");
+        if remotes_dir.join(old_name).exists() {
+            fs::rename(remotes_dir.join(old_name), remotes_dir.join(new_name))?;
+        }
+        println!("remote '{}' renamed to '{}'.", old_name, new_name);
+        Ok(())
+    })
+}
+
+pub fn remote_set_url(name: &str, url: &str) {
+    update_remotes(|meta, _| {
+        if meta.get_remote(name).is_none() {
+            return Err(Error::other(format!("no such remote '{}'", name)));
+        }
+        meta.set_remote(name.to_string(), url.to_string());
+        println!("remote.{} = {}", name, url);
+        Ok(())
+    })
+}
+
+pub fn remote_set_default(name: &str) {
+    update_remotes(|meta, _| {
+        if meta.get_remote(name).is_none() {
+            return Err(Error::other(format!("no such remote '{}'", name)));
+        }
+        meta.default_remote = name.to_string();
+        println!("default remote is now '{}'.", name);
+        Ok(())
+    })
+}
+
+fn update_remotes(update: impl FnOnce(&mut RepoMeta, PathBuf) -> Result<(), Error>) {
+    match check_svc_repo() {
+        Ok(svc_path) => {
+            let mut meta = RepoMeta::read_repo_meta(svc_path.clone());
+            if let Err(err) = update(&mut meta, svc_path.clone()).and_then(|_| meta.write_repo_meta(svc_path)) {
+                eprintln!("error: {}", err);
+                process::exit(1);
+            }
+        }
+        Err(err) => {
+            eprintln!("{}", err);
+            process::exit(1)
+        }
+    }
+}
+
+fn check_remote_name(name: &str) -> Result<(), Error> {
+    if name.is_empty()
+        || !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+    {
+        return Err(Error::other(format!("invalid remote name '{}'", name)));
+    }
+    Ok(())
+}
+
+pub fn serve(bind: &str, stdio: bool, path: &Option<String>) {
+    let svc_path = match path {
+        Some(path) => match LocalTransport::open(PathBuf::from(path)) {
+            Ok(repo) => repo.svc_path,
+            Err(err) => {
+                eprintln!("error: {}", err);
+                process::exit(1)
+            }
+        },
+        None => match check_svc_repo() {
+            Ok(svc_path) => svc_path,
+            Err(err) => {
+                eprintln!("{}", err);
+                process::exit(1)
+            }
+        },
+    };
+    let result = if stdio {
+        command::serve_stdio(svc_path)
+    } else {
+        http::serve_http(svc_path, bind)
+    };
+    if let Err(err) = result {
+        eprintln!("error: {}", err);
+        process::exit(1);
+    }
+}
+
+fn open_remote(svc_path: PathBuf, name: &Option<String>) -> Result<(String, Box<dyn Transport>), Error> {
+    let meta = RepoMeta::read_repo_meta(svc_path.clone());
+    let name = name.clone().unwrap_or(meta.default_remote.clone());
+    let remote = match meta.get_remote(&name) {
+        Some(remote) if !remote.url.is_empty() => remote,
+        _ if meta.remotes.is_empty() => {
+            return Err(Error::other("no remote yet, run \"svc remote add <name> <url>\" first"))
+        }
+        _ => return Err(Error::other(format!("no such remote '{}'", name))),
+    };
+    Ok((name.clone(), open_transport(&remote.url, svc_path.parent().unwrap().to_path_buf())?))
+}
@@ -0,0 +1,122 @@
+use crate::bucket::{check_object, Commit, ObjectType, RepoMeta, TreeEntry, NO_PARENT};
+use crate::remote::transport::Transport;
+use std::{collections::HashSet, io::Error};
+
+// Requests of the sync protocol, shared by every transport talking to a
+// remote svc process. Each request is a command, an optional argument and a
+// body, and gets a body back.
+pub const CMD_LOG: &str = "log";
+pub const CMD_HEAD: &str = "head";
+pub const CMD_META: &str = "meta";
+pub const CMD_HAS: &str = "has";
+pub const CMD_READ: &str = "read";
+pub const CMD_WRITE: &str = "write";
+pub const CMD_UPDATE: &str = "update";
+
+/// Serve a single request against a local repo.
+pub fn handle_request(repo: &mut dyn Transport, command: &str, arg: &str, body: &[u8]) -> Result<Vec<u8>, Error> {
+    match command {
+        CMD_LOG => Ok(repo.read_log()?.into_bytes()),
+        CMD_HEAD => Ok(repo.read_head()?.into_bytes()),
+        CMD_META => Ok(RepoMeta::parse_repo_meta(&repo.read_meta()?).public_meta().into_bytes()),
+        CMD_HAS => {
+            let has = repo.has_objects(&decode_hashes(body))?;
+            Ok(encode_has(&has).into_bytes())
+        }
+        CMD_READ => repo.read_object(arg),
+        CMD_WRITE => {
+            if !check_object(arg, body) {
+                return Err(Error::other(format!("object {} doesn't match its hash", arg)));
+            }
+            repo.write_object(arg, body)?;
+            Ok(Vec::new())
+        }
+        CMD_UPDATE => {
+            let (old_latest, log) = decode_update(body);
+            check_update(repo, &log)?;
+            repo.update_log(&old_latest, &log)?;
+            Ok(Vec::new())
+        }
+        _ => Err(Error::other(format!("unknown request '{}'", command))),
+    }
+}
+
+// a client may only add commits, whose parents and objects are all there
+fn check_update(repo: &mut dyn Transport, log: &str) -> Result<(), Error> {
+    let old_commits = Commit::parse_log(&repo.read_log()?);
+    let new_commits = Commit::parse_log(log);
+    let new_hashes: HashSet<&str> = new_commits.iter().map(|commit| commit.hash.as_str()).collect();
+    if let Some(commit) = old_commits.iter().find(|commit| !new_hashes.contains(commit.hash.as_str())) {
+        return Err(Error::other(format!("update drops commit {}, refusing it", commit.hash)));
+    }
+    let old_hashes: HashSet<&str> = old_commits.iter().map(|commit| commit.hash.as_str()).collect();
+    let mut checked = HashSet::new();
+    for commit in new_commits.iter().filter(|commit| !old_hashes.contains(commit.hash.as_str())) {
+        for parent in commit.parent_hash.split(',') {
+            if parent != NO_PARENT && !new_hashes.contains(parent) {
+                return Err(Error::other(format!("parent {} of commit {} is not in the log", parent, commit.hash)));
+            }
+        }
+        check_tree(repo, &commit.tree_hash, &mut checked)?;
+    }
+    Ok(())
+}
+
+// a tree is complete when every object below it is there
+fn check_tree(repo: &mut dyn Transport, tree_hash: &str, checked: &mut HashSet<String>) -> Result<(), Error> {
+    if !checked.insert(tree_hash.to_string()) {
+        return Ok(());
+    }
+    if !repo.has_objects(&[tree_hash.to_string()])?[0] {
+        return Err(Error::other(format!("object {} is missing", tree_hash)));
+    }
+    let entries = TreeEntry::parse_tree(&String::from_utf8_lossy(&repo.read_object(tree_hash)?))?;
+    let mut blobs = Vec::new();
+    for entry in entries {
+        match entry.object_type {
+            ObjectType::ObjectTree => check_tree(repo, &entry.hash, checked)?,
+            _ => blobs.push(entry.hash),
+        }
+    }
+    if let Some((hash, _)) = blobs.iter().zip(repo.has_objects(&blobs)?).find(|(_, has)| !has) {
+        return Err(Error::other(format!("object {} is missing", hash)));
+    }
+    Ok(())
+}
+
+pub fn encode_hashes(hashes: &[String]) -> String {
+    hashes.join("\n")
+}
+
+pub fn decode_hashes(body: &[u8]) -> Vec<String> {
+    String::from_utf8_lossy(body)
+        .lines()
+        .filter(|line| !line.is_empty())
+        .map(|line| line.to_string())
+        .collect()
+}
+
+pub fn encode_has(has: &[bool]) -> String {
+    has.iter().map(|has| if *has { "1" } else { "0" }).collect::<Vec<_>>().join("\n")
+}
+
+pub fn decode_has(body: &[u8], count: usize) -> Result<Vec<bool>, Error> {
+    let has: Vec<bool> = String::from_utf8_lossy(body).lines().map(|line| line == "1").collect();
+    if has.len() != count {
+        return Err(Error::other("malformed response from remote"));
+    }
+    Ok(has)
+}
+
+// the expected latest commit goes on the first line, followed by the new log
+pub fn encode_update(old_latest: &str, log: &str) -> String {
+    format!("{}\n{}", old_latest, log)
+}
+
+pub fn decode_update(body: &[u8]) -> (String, String) {
+    let body = String::from_utf8_lossy(body);
+    match body.split_once('\n') {
+        Some((old_latest, log)) => (old_latest.to_string(), log.to_string()),
+        None => (body.to_string(), String::new()),
+    }
+}
@@ -0,0 +1,197 @@
+use crate::bucket::{check_workspace, get_tree_of_commit, merge_into_head, write_remote_log, Commit, ObjectType, RepoMeta, TreeEntry, DEFAULT_REMOTE};
+use crate::remote::{bundle::BundleTransport, local::LocalTransport, transport::Transport};
+use std::{io::Error, path::PathBuf};
+
+pub fn push(remote: &mut dyn Transport, svc_path: PathBuf, name: &str) -> Result<(), Error> {
+    let mut local = LocalTransport::new(svc_path.clone());
+    let local_log = local.read_log()?;
+    let local_commits = Commit::parse_log(&local_log);
+    let remote_log = remote.read_log()?;
+    let remote_commits = Commit::parse_log(&remote_log);
+    write_remote_log(svc_path.clone(), name, &remote_log)?;
+    let local_latest = match local_commits.last() {
+        Some(commit) => commit.hash.clone(),
+        None => return Err(Error::other("no commit yet")),
+    };
+    let remote_latest = match remote_commits.last() {
+        Some(commit) => commit.hash.clone(),
+        None => String::new(),
+    };
+    if local_latest == remote_latest {
+        println!("everything up-to-date.");
+        return Ok(());
+    }
+    if !remote_latest.is_empty() && !contains_commit(&local_commits, &remote_latest) {
+        if contains_commit(&remote_commits, &local_latest) {
+            return Err(Error::other("remote has commits you don't have, run \"svc pull\" first"));
+        }
+        return Err(Error::other("histories have diverged, refusing to push"));
+    }
+
+    let mut pushed = 0;
+    for commit in &local_commits {
+        if !contains_commit(&remote_commits, &commit.hash) {
+            copy_tree(&mut local, remote, &commit.tree_hash)?;
+            pushed += 1;
+        }
+    }
+    remote.update_log(&remote_latest, &local_log)?;
+    write_remote_log(svc_path, name, &local_log)?;
+    println!("push {} commit(s), remote is now at {}.", pushed, local_latest);
+    Ok(())
+}
+
+pub fn pull(remote: &mut dyn Transport, svc_path: PathBuf, name: &str) -> Result<(), Error> {
+    let remote_log = remote.read_log()?;
+    write_remote_log(svc_path.clone(), name, &remote_log)?;
+    pull_log(remote, svc_path, &remote_log, name)
+}
+
+// import a bundle like a pull from an unnamed remote
+pub fn unbundle(bundle: &mut BundleTransport, svc_path: PathBuf, label: &str) -> Result<(), Error> {
+    let local_commits = Commit::read_from_log(svc_path.clone());
+    for hash in &bundle.requires {
+        if !contains_commit(&local_commits, hash) {
+            return Err(Error::other(format!("bundle requires commit {} you don't have", hash)));
+        }
+    }
+    let remote_log = bundle.read_log()?;
+    pull_log(bundle, svc_path, &remote_log, label)
+}
+
+fn pull_log(remote: &mut dyn Transport, svc_path: PathBuf, remote_log: &str, name: &str) -> Result<(), Error> {
+    let mut local = LocalTransport::new(svc_path.clone());
+    let local_commits = Commit::parse_log(&local.read_log()?);
+    let remote_commits = Commit::parse_log(remote_log);
+    let (remote_latest, remote_tree) = match remote_commits.last() {
+        Some(commit) => (commit.hash.clone(), commit.tree_hash.clone()),
+        None => return Err(Error::other("remote has no commit yet")),
+    };
+    let local_latest = match local_commits.last() {
+        Some(commit) => commit.hash.clone(),
+        None => String::new(),
+    };
+    if local_latest == remote_latest {
+        println!("already up-to-date.");
+        return Ok(());
+    }
+    if contains_commit(&local_commits, &remote_latest) {
+        println!("local repo is ahead of remote, nothing to pull.");
+        return Ok(());
+    }
+    let pulled = fetch_commits(remote, &mut local, &local_commits, &remote_commits)?;
+    if !local_latest.is_empty() && !contains_commit(&remote_commits, &local_latest) {
+        // histories have diverged, merge the remote into HEAD
+        println!("pull {} commit(s).", pulled);
+        let new_commits = remote_commits
+            .into_iter()
+            .filter(|commit| !contains_commit(&local_commits, &commit.hash))
+            .collect();
+        let message = format!("merge '{}'", name);
+        return merge_into_head(svc_path, &remote_latest, new_commits, name, message);
+    }
+
+    let head_tree = get_tree_of_commit(svc_path.clone(), Commit::get_head_hash(svc_path.clone()));
+    check_workspace(svc_path.clone(), head_tree.as_deref().ok(), &remote_tree, "pull")?;
+    Commit::restore_tree(
+        svc_path.parent().unwrap().to_path_buf(),
+        svc_path.clone(),
+        remote_tree.clone(),
+    )?;
+    if let Ok(head_tree) = head_tree {
+        Commit::remove_stale_files(svc_path.clone(), &head_tree, &remote_tree)?;
+        Commit::remove_stale_dirs(svc_path.clone(), head_tree, remote_tree)?;
+    }
+    local.write_log(&local_latest, remote_log)?;
+    Commit::reset_head(svc_path, remote_latest.clone());
+    println!("pull {} commit(s), switch to commit {}.", pulled, remote_latest);
+    Ok(())
+}
+
+pub fn fetch(remote: &mut dyn Transport, svc_path: PathBuf, name: &str) -> Result<(), Error> {
+    let mut local = LocalTransport::new(svc_path.clone());
+    let local_commits = Commit::parse_log(&local.read_log()?);
+    let remote_log = remote.read_log()?;
+    let remote_commits = Commit::parse_log(&remote_log);
+    let fetched = fetch_commits(remote, &mut local, &local_commits, &remote_commits)?;
+    write_remote_log(svc_path, name, &remote_log)?;
+    println!("fetch {} new commit(s) from '{}'.", fetched, name);
+    Ok(())
+}
+
+pub fn clone(remote: &mut dyn Transport, svc_path: PathBuf, url: &str) -> Result<(), Error> {
+    let mut local = LocalTransport::new(svc_path.clone());
+    let remote_log = remote.read_log()?;
+    let remote_commits = Commit::parse_log(&remote_log);
+    fetch_commits(remote, &mut local, &[], &remote_commits)?;
+    write_remote_log(svc_path.clone(), DEFAULT_REMOTE, &remote_log)?;
+
+    let remote_meta = RepoMeta::parse_repo_meta(&remote.read_meta()?);
+    let mut meta = RepoMeta::new(remote_meta.repo_name, remote_meta.repo_intro, url.to_string());
+    meta.created_at = remote_meta.created_at;
+    meta.updated_at = remote_meta.updated_at;
+    meta.write_repo_meta(svc_path.clone())?;
+    local.write_log("", &remote_log)?;
+
+    let mut head = remote.read_head()?;
+    if !contains_commit(&remote_commits, &head) {
+        head = match remote_commits.last() {
+            Some(commit) => commit.hash.clone(),
+            None => {
+                println!("warning: cloned an empty repo.");
+                return Ok(());
+            }
+        };
+    }
+    let tree_hash = get_tree_of_commit(svc_path.clone(), head.clone()).unwrap();
+    Commit::restore_tree(svc_path.parent().unwrap().to_path_buf(), svc_path.clone(), tree_hash)?;
+    Commit::reset_head(svc_path, head.clone());
+    println!("clone {} commit(s), switch to commit {}.", remote_commits.len(), head);
+    Ok(())
+}
+
+// copy the objects of remote commits we don't have yet
+fn fetch_commits(
+    remote: &mut dyn Transport,
+    local: &mut LocalTransport,
+    local_commits: &[Commit],
+    remote_commits: &[Commit],
+) -> Result<usize, Error> {
+    let mut fetched = 0;
+    for commit in remote_commits {
+        if !contains_commit(local_commits, &commit.hash) {
+            copy_tree(remote, local, &commit.tree_hash)?;
+            fetched += 1;
+        }
+    }
+    Ok(fetched)
+}
+
+fn contains_commit(commits: &[Commit], hash: &str) -> bool {
+    commits.iter().any(|commit| commit.hash == hash)
+}
+
+// copy a tree and everything it refers to that `to` is missing, children are
+// written before their tree so that a present tree is always complete
+pub fn copy_tree(from: &mut dyn Transport, to: &mut dyn Transport, tree_hash: &str) -> Result<(), Error> {
+    if to.has_objects(&[tree_hash.to_string()])?[0] {
+        return Ok(());
+    }
+    let data = from.read_object(tree_hash)?;
+    let entries = TreeEntry::parse_tree(&String::from_utf8_lossy(&data))?;
+    let hashes: Vec<String> = entries.iter().map(|entry| entry.hash.clone()).collect();
+    let has = to.has_objects(&hashes)?;
+    for (entry, has) in entries.iter().zip(has) {
+        if has {
+            continue;
+        }
+        match entry.object_type {
+            ObjectType::ObjectTree => copy_tree(from, to, &entry.hash)?,
+            ObjectType::ObjectBlob | ObjectType::ObjectLink => {
+                let blob = from.read_object(&entry.hash)?;
+                to.write_object(&entry.hash, &blob)?;
+            }
+        }
+    }
+    to.write_object(tree_hash, &data)
+}
@@ -0,0 +1,61 @@
+use crate::remote::{bundle::BundleTransport, command::CommandTransport, http::HttpTransport, local::LocalTransport};
+use std::{io::Error, path::PathBuf};
+
+/// Operations a remote repository has to support for push and pull.
+pub trait Transport {
+    /// Read the whole log of the remote, oldest commit first.
+    fn read_log(&mut self) -> Result<String, Error>;
+
+    /// Read the commit the remote workspace is at.
+    fn read_head(&mut self) -> Result<String, Error>;
+
+    /// Read the metadata of the remote, as stored in `.svc/repo`.
+    fn read_meta(&mut self) -> Result<String, Error>;
+
+    /// Tell which of the given objects the remote already has.
+    fn has_objects(&mut self, hashes: &[String]) -> Result<Vec<bool>, Error>;
+
+    /// Read an object file exactly as it is stored.
+    fn read_object(&mut self, hash: &str) -> Result<Vec<u8>, Error>;
+
+    /// Store an object file exactly as it was read.
+    fn write_object(&mut self, hash: &str, data: &[u8]) -> Result<(), Error>;
+
+    /// Replace the log, as long as its latest commit is still `old_latest`.
+    fn update_log(&mut self, old_latest: &str, log: &str) -> Result<(), Error>;
+}
+
+pub fn open_transport(url: &str, root_path: PathBuf) -> Result<Box<dyn Transport>, Error> {
+    if let Some(path) = url.strip_prefix("file://") {
+        return open_path(PathBuf::from(path));
+    }
+    if url.starts_with("http://") {
+        return Ok(Box::new(HttpTransport::new(url)));
+    }
+    if url.starts_with("ssh://") {
+        return Ok(Box::new(CommandTransport::ssh(url)?));
+    }
+    if url.starts_with("ext::") {
+        return Ok(Box::new(CommandTransport::ext(url)?));
+    }
+    if url.contains("://") {
+        return Err(Error::other(format!("unsupported remote url '{}'", url)));
+    }
+    // plain paths are relative to the workspace root
+    open_path(root_path.join(url))
+}
+
+// a path is either a repo or a bundle file
+fn open_path(path: PathBuf) -> Result<Box<dyn Transport>, Error> {
+    if BundleTransport::is_bundle(&path) {
+        return Ok(Box::new(BundleTransport::open(path)?));
+    }
+    Ok(Box::new(LocalTransport::open(path)?))
+}
+
+pub fn check_hash(hash: &str) -> Result<(), Error> {
+    if hash.len() != 40 || !hash.chars().all(|c| c.is_ascii_hexdigit()) {
+        return Err(Error::other(format!("invalid object hash '{}'", hash)));
+    }
+    Ok(())
+}
@@ -1,20 +1,26 @@
-use std::io::{Write, Read};
+use std::io::{Error, Write, Read};
 
 use flate2::{
-    read::ZlibDecoder,
+    bufread::ZlibDecoder,
     write::ZlibEncoder,
     Compression
 };
 
 pub fn compress_data(data: &[u8]) -> Vec<u8>{
     let mut encoder = ZlibEncoder::new(Vec::new(), Compression::fast());
-    encoder.write(data).unwrap();
+    encoder.write_all(data).unwrap();
     encoder.finish().unwrap()
 }
 
-pub fn decompress_data(data: &[u8]) -> Vec<u8> {
-    let mut decoder = ZlibDecoder::new(data);
+// data that may be corrupted gives an error
+pub fn try_decompress_data(data: &[u8]) -> Result<Vec<u8>, Error> {
+    // older blobs were saved as a sequence of zlib streams, one per chunk
     let mut decompressd_data = Vec::new();
-    decoder.read_to_end(&mut decompressd_data).unwrap();
-    decompressd_data
-}
\ No newline at end of file
+    let mut rest = data;
+    while !rest.is_empty() {
+        let mut decoder = ZlibDecoder::new(rest);
+        decoder.read_to_end(&mut decompressd_data)?;
+        rest = decoder.into_inner();
+    }
+    Ok(decompressd_data)
+}
@@ -0,0 +1,212 @@
+// Line based diff (Myers' algorithm) and three-way merge of text.
+
+pub fn split_lines(data: &[u8]) -> Vec<&[u8]> {
+    data.split_inclusive(|byte| *byte == b'\n').collect()
+}
+
+pub fn is_text(data: &[u8]) -> bool {
+    !data.contains(&0) && std::str::from_utf8(data).is_ok()
+}
+
+/// For every line of `a`, the line of `b` it is paired with in a longest
+/// common subsequence of both.
+pub fn match_lines(a: &[&[u8]], b: &[&[u8]]) -> Vec<Option<usize>> {
+    let n = a.len() as isize;
+    let m = b.len() as isize;
+    let max = n + m;
+    let offset = max + 1;
+    let mut v = vec![0isize; (2 * max + 3) as usize];
+    // trace[d] keeps v[-d..=d] as it was before step d
+    let mut trace: Vec<Vec<isize>> = Vec::new();
+    'search: for d in 0..=max {
+        trace.push(v[(offset - d) as usize..=(offset + d) as usize].to_vec());
+        let mut k = -d;
+        while k <= d {
+            let index = (k + offset) as usize;
+            let mut x = if k == -d || (k != d && v[index - 1] < v[index + 1]) {
+                v[index + 1]
+            } else {
+                v[index - 1] + 1
+            };
+            let mut y = x - k;
+            while x < n && y < m && a[x as usize] == b[y as usize] {
+                x += 1;
+                y += 1;
+            }
+            v[index] = x;
+            if x >= n && y >= m {
+                break 'search;
+            }
+            k += 2;
+        }
+    }
+
+    let mut matches = vec![None; a.len()];
+    let (mut x, mut y) = (n, m);
+    for d in (0..trace.len() as isize).rev() {
+        let v = &trace[d as usize];
+        let get = |k: isize| v[(k + d) as usize];
+        let k = x - y;
+        let prev_k = if k == -d || (k != d && get(k - 1) < get(k + 1)) {
+            k + 1
+        } else {
+            k - 1
+        };
+        let (prev_x, prev_y) = if d == 0 { (0, 0) } else { (get(prev_k), get(prev_k) - prev_k) };
+        while x > prev_x && y > prev_y {
+            x -= 1;
+            y -= 1;
+            matches[x as usize] = Some(y as usize);
+        }
+        x = prev_x;
+        y = prev_y;
+    }
+    matches
+}
+
+/// Merge the changes `ours` and `theirs` made to `base`, returning the merged
+/// text and whether conflict markers had to be written.
+pub fn merge_lines(base: &[u8], ours: &[u8], theirs: &[u8], ours_label: &str, theirs_label: &str) -> (Vec<u8>, bool) {
+    let base = split_lines(base);
+    let ours = split_lines(ours);
+    let theirs = split_lines(theirs);
+    let ours_matches = match_lines(&base, &ours);
+    let theirs_matches = match_lines(&base, &theirs);
+
+    let mut merged: Vec<u8> = Vec::new();
+    let mut conflict = false;
+    let (mut i, mut j, mut k) = (0, 0, 0);
+    loop {
+        // the next base line kept by both sides
+        let mut next = i;
+        while next < base.len() && (ours_matches[next].is_none() || theirs_matches[next].is_none()) {
+            next += 1;
+        }
+        let (next_j, next_k) = if next < base.len() {
+            (ours_matches[next].unwrap(), theirs_matches[next].unwrap())
+        } else {
+            (ours.len(), theirs.len())
+        };
+        let base_chunk = &base[i..next];
+        let ours_chunk = &ours[j..next_j];
+        let theirs_chunk = &theirs[k..next_k];
+        if ours_chunk == base_chunk {
+            merged.extend(theirs_chunk.concat());
+        } else if theirs_chunk == base_chunk || ours_chunk == theirs_chunk {
+            merged.extend(ours_chunk.concat());
+        } else {
+            conflict = true;
+            merged.extend(format!("<<<<<<< {}\n", ours_label).as_bytes());
+            push_chunk(&mut merged, ours_chunk);
+            merged.extend(b"=======\n");
+            push_chunk(&mut merged, theirs_chunk);
+            merged.extend(format!(">>>>>>> {}\n", theirs_label).as_bytes());
+        }
+        if next >= base.len() {
+            break;
+        }
+        merged.extend(base[next]);
+        i = next + 1;
+        j = next_j + 1;
+        k = next_k + 1;
+    }
+    (merged, conflict)
+}
+
+// a conflicting chunk always ends with a line break, so markers start a line
+fn push_chunk(merged: &mut Vec<u8>, chunk: &[&[u8]]) {
+    merged.extend(chunk.concat());
+    if merged.last().is_some_and(|byte| *byte != b'\n') {
+        merged.push(b'\n');
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(text: &str) -> Vec<&[u8]> {
+        split_lines(text.as_bytes())
+    }
+
+    // length of a longest common subsequence, the slow way
+    fn lcs_len(a: &[&[u8]], b: &[&[u8]]) -> usize {
+        let mut table = vec![vec![0; b.len() + 1]; a.len() + 1];
+        for i in (0..a.len()).rev() {
+            for j in (0..b.len()).rev() {
+                table[i][j] = if a[i] == b[j] { table[i + 1][j + 1] + 1 } else { table[i + 1][j].max(table[i][j + 1]) };
+            }
+        }
+        table[0][0]
+    }
+
+    fn check_matches(a: &str, b: &str) {
+        let (a, b) = (lines(a), lines(b));
+        let matches = match_lines(&a, &b);
+        assert_eq!(matches.len(), a.len());
+        let mut last = None;
+        for (i, matched) in matches.iter().enumerate() {
+            if let Some(j) = *matched {
+                assert_eq!(a[i], b[j]);
+                assert!(last.is_none_or(|last| j > last));
+                last = Some(j);
+            }
+        }
+        assert_eq!(matches.iter().flatten().count(), lcs_len(&a, &b));
+    }
+
+    #[test]
+    fn match_lines_finds_longest_common_lines() {
+        check_matches("", "");
+        check_matches("a\n", "");
+        check_matches("", "a\n");
+        check_matches("a\nb\nc\n", "a\nb\nc\n");
+        check_matches("a\nb\nc\n", "x\ny\nz\n");
+        check_matches("a\nb\nc\na\nb\nb\na\n", "c\nb\na\nb\na\nc\n");
+        check_matches("a\nb\nc\nd\n", "a\nx\nc\ny\n");
+        check_matches("x\na\nx\nb\nx\n", "a\nx\nx\nb\n");
+        assert_eq!(match_lines(&lines("a\nb\n"), &lines("b\n")), vec![None, Some(0)]);
+    }
+
+    #[test]
+    fn merge_changes_of_both_sides() {
+        let base = "a\nb\nc\nd\ne\n";
+        let (merged, conflict) = merge_lines(base.as_bytes(), b"A\nb\nc\nd\ne\n", b"a\nb\nc\nd\nE\n", "ours", "theirs");
+        assert!(!conflict);
+        assert_eq!(merged, b"A\nb\nc\nd\nE\n");
+
+        // a line removed on one side and another added on the other
+        let (merged, conflict) = merge_lines(base.as_bytes(), b"a\nc\nd\ne\n", b"a\nb\nc\nd\ne\nf\n", "ours", "theirs");
+        assert!(!conflict);
+        assert_eq!(merged, b"a\nc\nd\ne\nf\n");
+
+        // the same change on both sides
+        let (merged, conflict) = merge_lines(base.as_bytes(), b"a\nB\nc\nd\ne\n", b"a\nB\nc\nd\ne\n", "ours", "theirs");
+        assert!(!conflict);
+        assert_eq!(merged, b"a\nB\nc\nd\ne\n");
+
+        let (merged, conflict) = merge_lines(b"", b"", b"new\n", "ours", "theirs");
+        assert!(!conflict);
+        assert_eq!(merged, b"new\n");
+    }
+
+    #[test]
+    fn merge_marks_conflicts() {
+        let (merged, conflict) = merge_lines(b"a\nb\nc\n", b"a\nours\nc\n", b"a\ntheirs\nc\n", "HEAD", "other");
+        assert!(conflict);
+        assert_eq!(merged, b"a\n<<<<<<< HEAD\nours\n=======\ntheirs\n>>>>>>> other\nc\n");
+
+        // markers always start a line, even without a final line break
+        let (merged, conflict) = merge_lines(b"a", b"b", b"c", "HEAD", "other");
+        assert!(conflict);
+        assert_eq!(merged, b"<<<<<<< HEAD\nb\n=======\nc\n>>>>>>> other\n");
+    }
+
+    #[test]
+    fn detect_text() {
+        assert!(is_text(b"plain\ntext\n"));
+        assert!(is_text("ünïcode".as_bytes()));
+        assert!(!is_text(b"a\0b"));
+        assert!(!is_text(&[0xff, 0xfe]));
+    }
+}
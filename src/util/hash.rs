@@ -26,4 +26,11 @@ pub fn get_str_hash(str: &str) -> String {
     hasher.update(str);
     let hash = format!("{:x}", hasher.finalize());
     hash
-}
\ No newline at end of file
+}
+
+pub fn get_data_hash(data: &[u8]) -> String {
+    let mut hasher = Sha1::new();
+    hasher.update(data);
+    let hash = format!("{:x}", hasher.finalize());
+    hash
+}
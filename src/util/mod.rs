@@ -1,5 +1,7 @@
 mod hash;
 mod compress;
+mod diff;
 
-pub use hash::{get_file_hash, get_str_hash};
-pub use compress::{compress_data, decompress_data};
\ No newline at end of file
+pub use hash::{get_data_hash, get_file_hash, get_str_hash};
+pub use diff::{is_text, merge_lines};
+pub use compress::{compress_data, try_decompress_data};
\ No newline at end of file
@@ -0,0 +1,56 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn amend_and_reword_head() {
+    let tmp = TempDir::new("amend");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    let typo = commit(&a, "secnod");
+
+    // a new message only, the files stay as they are
+    svc_ok(&a, &["commit", "--amend", "-m", "second"]);
+    let reworded = head(&a);
+    assert_ne!(reworded, typo);
+    assert_eq!(log_hashes(&a), [first.clone(), reworded.clone()]);
+    assert!(read(&a, ".svc/log").lines().last().unwrap().ends_with(" second"));
+    assert_eq!(svc_ok(&a, &["cat", "f.txt"]), "two\n");
+
+    // a forgotten change, the message is kept
+    write(&a, "f.txt", "three\n");
+    write(&a, "g.txt", "forgotten\n");
+    svc_ok(&a, &["commit", "--amend"]);
+    let amended = head(&a);
+    assert_eq!(log_hashes(&a), [first.clone(), amended.clone()]);
+    assert!(read(&a, ".svc/log").lines().last().unwrap().ends_with(" second"));
+    assert_eq!(svc_ok(&a, &["cat", "g.txt"]), "forgotten\n");
+
+    // the replaced commits can still be read, but not checked out
+    assert!(read(&a, ".svc/amended").contains(&typo));
+    assert_eq!(svc_ok(&a, &["cat", "f.txt", "-v", &typo]), "two\n");
+    assert!(svc_ok(&a, &["show", &reworded]).contains("second"));
+    svc_err(&a, &["checkout", &typo]);
+    svc_ok(&a, &["restore", "--source", &typo, "f.txt"]);
+    assert_eq!(read(&a, "f.txt"), "two\n");
+    assert_eq!(head(&a), amended);
+}
+
+#[test]
+fn amend_drops_the_commits_after_head() {
+    let tmp = TempDir::new("amend-latest");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    let second = commit(&a, "second");
+    svc_ok(&a, &["checkout", &first]);
+
+    svc_ok(&a, &["commit", "--amend", "-m", "first again"]);
+    assert_eq!(log_hashes(&a), [head(&a)]);
+    assert!(read(&a, ".svc/log.bak").contains(&second));
+}
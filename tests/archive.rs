@@ -0,0 +1,35 @@
+mod common;
+
+use common::*;
+use std::{fs, os::unix::fs::symlink, path::Path, thread, time::Duration};
+
+fn archive(dir: &Path, args: &[&str]) -> Vec<u8> {
+    let mut all = vec!["archive", "HEAD", "-o", "../out"];
+    all.extend_from_slice(args);
+    svc_ok(dir, &all);
+    fs::read(dir.join("../out")).unwrap()
+}
+
+#[test]
+fn archives_are_reproducible() {
+    let tmp = TempDir::new("archive");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/run.sh", "#!/bin/sh\n");
+    symlink("f.txt", a.join("link")).unwrap();
+    commit(&a, "first");
+
+    let tar = archive(&a, &["--prefix", "project/"]);
+    assert!(tar.windows(18).any(|name| name == b"project/dir/run.sh"));
+    // the same bytes from another workspace, written at another time
+    thread::sleep(Duration::from_millis(1100));
+    svc_ok(tmp.path(), &["clone", "a", "b"]);
+    let b = tmp.join("b");
+    for format in ["tar", "tar.gz", "zip"] {
+        assert_eq!(archive(&a, &["--format", format]), archive(&b, &["--format", format]), "{}", format);
+    }
+    assert_eq!(archive(&b, &["--prefix", "project/"]), tar);
+    assert_ne!(archive(&a, &["--format", "zip"]), archive(&a, &["--format", "tar"]));
+    svc_err(&a, &["archive", "HEAD", "-o", "../out", "--format", "rar"]);
+}
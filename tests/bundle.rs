@@ -0,0 +1,61 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn bundle_round_trip() {
+    let tmp = TempDir::new("bundle");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/data.bin", b"\x00\x01\x02");
+    commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    let second = commit(&a, "second");
+
+    svc_ok(&a, &["bundle", "create", "../full.bundle"]);
+    svc_ok(tmp.path(), &["clone", "full.bundle", "b"]);
+    let b = tmp.join("b");
+    assert_eq!(log_hashes(&b), log_hashes(&a));
+    assert_eq!(read(&b, "f.txt"), "two\n");
+    assert_eq!(fs::read(b.join("dir/data.bin")).unwrap(), b"\x00\x01\x02");
+
+    // only what a receiver at the second commit is missing
+    write(&a, "f.txt", "three\n");
+    commit(&a, "third");
+    write(&a, "g.txt", "four\n");
+    let latest = commit(&a, "fourth");
+    svc_ok(&a, &["bundle", "create", "../new.bundle", &format!("{}..", second)]);
+    svc_ok(&b, &["bundle", "unbundle", "../new.bundle"]);
+    assert_eq!(log_hashes(&b), log_hashes(&a));
+    assert_eq!(head(&b), latest);
+    assert_eq!(read(&b, "g.txt"), "four\n");
+
+    // a repo without the base commit can't take it
+    let c = tmp.join("c");
+    init_repo(&c);
+    svc_err(&c, &["bundle", "unbundle", "../new.bundle"]);
+    assert!(log_hashes(&c).is_empty());
+    svc_err(&b, &["push", "../new.bundle"]);
+}
+
+#[test]
+fn corrupted_bundles_are_refused() {
+    let tmp = TempDir::new("bundle-corrupt");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    svc_ok(&a, &["bundle", "create", "../a.bundle"]);
+
+    let mut data = fs::read(tmp.join("a.bundle")).unwrap();
+    let last = data.len() - 1;
+    data[last] ^= 1;
+    fs::write(tmp.join("bad.bundle"), data).unwrap();
+    let c = tmp.join("c");
+    init_repo(&c);
+    svc_err(&c, &["bundle", "unbundle", "../bad.bundle"]);
+    assert!(log_hashes(&c).is_empty());
+    svc_err(tmp.path(), &["clone", "bad.bundle", "d"]);
+}
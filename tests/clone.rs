@@ -0,0 +1,48 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn clone_into_new_and_existing_directories() {
+    let tmp = TempDir::new("clone");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    write(&a, "dir/g.txt", "two\n");
+    let latest = commit(&a, "second");
+
+    svc_ok(tmp.path(), &["clone", "a", "b"]);
+    let b = tmp.join("b");
+    assert_eq!(log_hashes(&b), log_hashes(&a));
+    assert_eq!(head(&b), latest);
+    assert_eq!(read(&b, "dir/g.txt"), "two\n");
+    // a plain path is kept absolute
+    assert_eq!(svc_ok(&b, &["remote", "list"]), format!("origin\t{} (default)\n", a.display()));
+
+    // the directory is named after the url when none is given
+    let other = tmp.join("other");
+    fs::create_dir(&other).unwrap();
+    svc_ok(&other, &["clone", "../a"]);
+    assert_eq!(head(&other.join("a")), latest);
+
+    // an existing directory has to be empty
+    let empty = tmp.join("empty");
+    fs::create_dir(&empty).unwrap();
+    svc_ok(tmp.path(), &["clone", "a", "empty"]);
+    assert_eq!(read(&empty, "f.txt"), "one\n");
+
+    write(&tmp.join("full"), "mine.txt", "mine\n");
+    assert!(svc_err(tmp.path(), &["clone", "a", "full"]).contains("is not empty"));
+    assert_eq!(read(&tmp.join("full"), "mine.txt"), "mine\n");
+    assert!(!tmp.join("full/.svc").exists());
+
+    // a failed clone leaves an existing directory as it was
+    let failed = tmp.join("failed");
+    fs::create_dir(&failed).unwrap();
+    svc_err(tmp.path(), &["clone", "missing", "failed"]);
+    assert!(failed.is_dir() && !failed.join(".svc").exists());
+    svc_err(tmp.path(), &["clone", "missing", "new"]);
+    assert!(!tmp.join("new").exists());
+}
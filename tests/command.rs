@@ -0,0 +1,72 @@
+mod common;
+
+use common::*;
+use std::{
+    env, fs,
+    os::unix::fs::PermissionsExt,
+    path::{Path, PathBuf},
+};
+
+// a url running `svc serve --stdio` on a repo through the shell
+fn ext_url(repo: &Path) -> String {
+    format!("ext::'{}' serve --stdio '{}'", svc_bin(), repo.display())
+}
+
+#[test]
+fn push_and_pull_over_ext() {
+    let tmp = TempDir::new("ext-sync");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+
+    svc_ok(tmp.path(), &["clone", &ext_url(&a), "b"]);
+    let b = tmp.join("b");
+    assert_eq!(read(&b, "f.txt"), "one\n");
+
+    write(&b, "f.txt", "two\n");
+    let pushed = commit(&b, "second");
+    svc_ok(&b, &["push"]);
+    assert_eq!(head(&a), pushed);
+    assert_eq!(read(&a, "f.txt"), "two\n");
+
+    write(&a, "g.txt", "three\n");
+    let latest = commit(&a, "third");
+    svc_ok(&b, &["pull"]);
+    assert_eq!(head(&b), latest);
+    assert_eq!(read(&b, "g.txt"), "three\n");
+
+    // a helper that fails is reported
+    svc_ok(&b, &["remote", "add", "broken", "ext::exit 1"]);
+    svc_err(&b, &["pull", "broken"]);
+}
+
+#[test]
+fn ssh_path_reaches_the_remote_shell_as_one_word() {
+    let tmp = TempDir::new("ssh-quote");
+    // a fake ssh dropping the port and host, then running the command like
+    // the remote shell would
+    let bin = tmp.join("bin");
+    fs::create_dir_all(&bin).unwrap();
+    let ssh = bin.join("fakessh");
+    fs::write(&ssh, "#!/bin/sh\n[ \"$1\" = -p ] && shift 2\necho \"$1\" >> \"$(dirname \"$0\")/hosts\"\nshift\nexec sh -c \"$1\"\n").unwrap();
+    fs::set_permissions(&ssh, fs::Permissions::from_mode(0o755)).unwrap();
+    fs::copy(svc_bin(), bin.join("svc")).unwrap();
+    let path = format!("{}:{}", bin.display(), env::var("PATH").unwrap_or_default());
+
+    let a = tmp.join("a b $(touch pwned)");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+
+    let mut clone = svc_command(tmp.path(), &["clone", &format!("ssh://me@[::1]:2222{}", a.display()), "b"]);
+    clone.env("SVC_SSH", &ssh).env("PATH", &path);
+    run_ok(clone);
+    assert_eq!(head(&tmp.join("b")), first);
+    assert_eq!(read(&bin, "hosts"), "me@::1\n");
+    assert!(!tmp.join("pwned").exists() && !PathBuf::from("pwned").exists());
+
+    let mut clone = svc_command(tmp.path(), &["clone", "ssh://-oProxyCommand=touch%20pwned/repo", "c"]);
+    clone.env("SVC_SSH", &ssh);
+    assert!(!clone.output().unwrap().status.success());
+}
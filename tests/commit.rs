@@ -0,0 +1,44 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn commit_only_the_given_paths() {
+    let tmp = TempDir::new("commit-paths");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, ".svcignore", "*.log\n");
+    write(&a, "f.txt", "one\n");
+    write(&a, "g.txt", "g\n");
+    write(&a, "dir/h.txt", "h\n");
+    write(&a, "dir/sub/i.txt", "i\n");
+    commit(&a, "first");
+
+    write(&a, "f.txt", "two\n");
+    write(&a, "g.txt", "unrelated\n");
+    write(&a, "dir/new.txt", "new\n");
+    fs::remove_file(a.join("dir/sub/i.txt")).unwrap();
+    write(&a, "dir/a.log", "ignored\n");
+    svc_ok(&a, &["commit", "-m", "second", "f.txt", "dir"]);
+    assert_eq!(svc_ok(&a, &["cat", "f.txt"]), "two\n");
+    assert_eq!(svc_ok(&a, &["cat", "g.txt"]), "g\n");
+    assert_eq!(svc_ok(&a, &["cat", "dir/new.txt"]), "new\n");
+    assert_eq!(svc_ok(&a, &["cat", "dir/h.txt"]), "h\n");
+    svc_err(&a, &["cat", "dir/sub/i.txt"]);
+    svc_err(&a, &["cat", "dir/a.log"]);
+
+    // a deleted file is saved as deleted, a path that never existed is an error
+    fs::remove_file(a.join("f.txt")).unwrap();
+    svc_ok(&a, &["commit", "-m", "third", "f.txt"]);
+    svc_err(&a, &["cat", "f.txt"]);
+    assert_eq!(svc_ok(&a, &["cat", "g.txt"]), "g\n");
+    let before = head(&a);
+    assert!(svc_err(&a, &["commit", "-m", "fourth", "nothing.txt"]).contains("'nothing.txt' did not match any file"));
+    assert_eq!(head(&a), before);
+
+    // the same with --amend
+    svc_ok(&a, &["commit", "--amend", "g.txt"]);
+    assert_eq!(svc_ok(&a, &["cat", "g.txt"]), "unrelated\n");
+    assert!(read(&a, ".svc/log").lines().last().unwrap().ends_with(" third"));
+}
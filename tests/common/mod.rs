@@ -0,0 +1,107 @@
+// Helpers running the svc binary on repos in temporary directories.
+#![allow(dead_code)]
+
+use std::{
+    env, fs,
+    path::{Path, PathBuf},
+    process::{self, Command, Output, Stdio},
+};
+
+/// A directory removed again when the test is done.
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    pub fn new(name: &str) -> TempDir {
+        let path = env::temp_dir().join(format!("svc-test-{}-{}", name, process::id()));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    pub fn join(&self, path: &str) -> PathBuf {
+        self.0.join(path)
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
+
+pub fn svc_bin() -> &'static str {
+    env!("CARGO_BIN_EXE_svc")
+}
+
+pub fn svc_command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new(svc_bin());
+    // "svc init" asks for a name on stdin
+    command.args(args).current_dir(dir).stdin(Stdio::null());
+    command
+}
+
+pub fn svc(dir: &Path, args: &[&str]) -> Output {
+    svc_command(dir, args).output().unwrap()
+}
+
+/// Run svc and return its output, failing the test when it fails.
+pub fn svc_ok(dir: &Path, args: &[&str]) -> String {
+    run_ok(svc_command(dir, args))
+}
+
+pub fn run_ok(mut command: Command) -> String {
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout).to_string();
+    assert!(
+        output.status.success(),
+        "{:?} failed:\n{}{}",
+        command,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    stdout
+}
+
+/// Run svc and return its error output, failing the test when it succeeds.
+pub fn svc_err(dir: &Path, args: &[&str]) -> String {
+    let output = svc(dir, args);
+    assert!(!output.status.success(), "svc {:?} should have failed", args);
+    String::from_utf8_lossy(&output.stderr).to_string()
+}
+
+pub fn init_repo(dir: &Path) {
+    fs::create_dir_all(dir).unwrap();
+    svc_ok(dir, &["init"]);
+}
+
+pub fn write(dir: &Path, path: &str, data: impl AsRef<[u8]>) {
+    let path = dir.join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, data).unwrap();
+}
+
+pub fn read(dir: &Path, path: &str) -> String {
+    fs::read_to_string(dir.join(path)).unwrap()
+}
+
+pub fn commit(dir: &Path, message: &str) -> String {
+    svc_ok(dir, &["commit", "-m", message]);
+    head(dir)
+}
+
+pub fn head(dir: &Path) -> String {
+    read(dir, ".svc/head").trim().to_string()
+}
+
+/// Hashes of the log, oldest first.
+pub fn log_hashes(dir: &Path) -> Vec<String> {
+    read(dir, ".svc/log")
+        .lines()
+        .filter_map(|line| line.split(' ').next())
+        .map(|hash| hash.to_string())
+        .collect()
+}
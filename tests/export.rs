@@ -0,0 +1,34 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn export_leaves_head_and_workspace_alone() {
+    let tmp = TempDir::new("export");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/g.txt", "g\n");
+    let first = commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    write(&a, "h.txt", "h\n");
+    let second = commit(&a, "second");
+    write(&a, "f.txt", "unsaved\n");
+
+    svc_ok(&a, &["export", &first, "../out"]);
+    let out = tmp.join("out");
+    assert_eq!(read(&out, "f.txt"), "one\n");
+    assert_eq!(read(&out, "dir/g.txt"), "g\n");
+    assert!(!out.join("h.txt").exists() && !out.join(".svc").exists());
+    assert_eq!(head(&a), second);
+    assert_eq!(read(&a, "f.txt"), "unsaved\n");
+
+    // only some paths, into a directory that has to be empty
+    svc_ok(&a, &["export", &second, "../part", "h.txt", "dir/"]);
+    let part = tmp.join("part");
+    assert_eq!(read(&part, "h.txt"), "h\n");
+    assert_eq!(read(&part, "dir/g.txt"), "g\n");
+    assert!(!part.join("f.txt").exists());
+    svc_err(&a, &["export", &second, "../part"]);
+    svc_err(&a, &["export", &first, "../missing", "h.txt"]);
+}
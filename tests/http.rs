@@ -0,0 +1,166 @@
+mod common;
+
+use common::*;
+use std::{
+    io::{Read, Write},
+    net::{TcpListener, TcpStream},
+    path::Path,
+    process::{Child, Command, Stdio},
+    thread,
+    time::Duration,
+};
+
+/// A `svc serve` process, stopped when dropped.
+struct Server {
+    child: Child,
+    port: u16,
+}
+
+impl Server {
+    fn start(dir: &Path) -> Server {
+        let port = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap().port();
+        let child = Command::new(svc_bin())
+            .args(["serve", "--bind", &format!("127.0.0.1:{}", port)])
+            .current_dir(dir)
+            .stdin(Stdio::null())
+            .stdout(Stdio::null())
+            .spawn()
+            .unwrap();
+        let server = Server { child, port };
+        for _ in 0..100 {
+            if TcpStream::connect(("127.0.0.1", port)).is_ok() {
+                return server;
+            }
+            thread::sleep(Duration::from_millis(50));
+        }
+        panic!("svc serve did not start");
+    }
+
+    fn url(&self) -> String {
+        format!("http://127.0.0.1:{}", self.port)
+    }
+
+    // a raw request, giving the status code and the body
+    fn request(&self, method: &str, path: &str, body: &[u8]) -> (u16, String) {
+        let mut stream = TcpStream::connect(("127.0.0.1", self.port)).unwrap();
+        let head = format!(
+            "{} /{} HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+            method,
+            path,
+            body.len()
+        );
+        stream.write_all(head.as_bytes()).unwrap();
+        stream.write_all(body).unwrap();
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        let response = String::from_utf8_lossy(&response).to_string();
+        let status = response.split(' ').nth(1).unwrap().parse().unwrap();
+        let body = response.split_once("\r\n\r\n").map(|(_, body)| body.to_string()).unwrap_or_default();
+        (status, body)
+    }
+}
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+#[test]
+fn push_and_pull_over_http() {
+    let tmp = TempDir::new("http-sync");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    let server = Server::start(&a);
+
+    svc_ok(tmp.path(), &["clone", &server.url(), "b"]);
+    let b = tmp.join("b");
+    assert_eq!(read(&b, "f.txt"), "one\n");
+    assert_eq!(log_hashes(&b), log_hashes(&a));
+
+    // a push moves the clean workspace of the served repo along
+    write(&b, "f.txt", "two\n");
+    write(&b, "dir/g.txt", "new\n");
+    let pushed = commit(&b, "second");
+    svc_ok(&b, &["push"]);
+    assert_eq!(log_hashes(&a), log_hashes(&b));
+    assert_eq!(head(&a), pushed);
+    assert_eq!(read(&a, "f.txt"), "two\n");
+    assert_eq!(read(&a, "dir/g.txt"), "new\n");
+
+    write(&a, "f.txt", "three\n");
+    let latest = commit(&a, "third");
+    svc_ok(&b, &["pull"]);
+    assert_eq!(head(&b), latest);
+    assert_eq!(read(&b, "f.txt"), "three\n");
+}
+
+#[test]
+fn diverged_push_is_refused_until_pulled() {
+    let tmp = TempDir::new("http-diverged");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    let server = Server::start(&a);
+    svc_ok(tmp.path(), &["clone", &server.url(), "b"]);
+    svc_ok(tmp.path(), &["clone", &server.url(), "c"]);
+    let (b, c) = (tmp.join("b"), tmp.join("c"));
+
+    write(&b, "b.txt", "b\n");
+    commit(&b, "from b");
+    svc_ok(&b, &["push"]);
+
+    write(&c, "c.txt", "c\n");
+    commit(&c, "from c");
+    assert!(svc_err(&c, &["push"]).contains("diverged"));
+    svc_ok(&c, &["pull"]);
+    assert_eq!(read(&c, "b.txt"), "b\n");
+    svc_ok(&c, &["push"]);
+    assert_eq!(log_hashes(&a), log_hashes(&c));
+    assert_eq!(read(&a, "c.txt"), "c\n");
+}
+
+#[test]
+fn server_checks_what_it_is_sent() {
+    let tmp = TempDir::new("http-checks");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+    svc_ok(&a, &["remote", "add", "secret", "http://secret.example/repo"]);
+    let server = Server::start(&a);
+
+    let (status, meta) = server.request("GET", "meta", b"");
+    assert_eq!(status, 200);
+    assert!(meta.contains("created_at"));
+    assert!(!meta.contains("secret") && !meta.contains("default_remote"));
+
+    // an object that isn't what its hash says
+    let hash = "0123456789abcdef0123456789abcdef01234567";
+    let (status, _) = server.request("PUT", &format!("objects/{}", hash), b"junk");
+    assert_eq!(status, 400);
+    assert!(!a.join(".svc/objects/01").join(&hash[2..]).exists());
+    // trees with entries that can't be read, or of an unknown version
+    for body in [format!("{} 1 3\n{} blob 1 999 f\n", hash, hash), format!("{} 0 7\n", hash)] {
+        let (status, _) = server.request("PUT", &format!("objects/{}", hash), body.as_bytes());
+        assert_eq!(status, 400);
+    }
+
+    // a log dropping the served commits
+    let (status, body) = server.request("POST", "update", format!("{}\n", first).as_bytes());
+    assert_eq!(status, 400);
+    assert!(body.contains("drops commit"));
+
+    // a commit whose tree was never sent
+    let log = read(&a, ".svc/log");
+    let missing = "ab".repeat(20);
+    let line = format!("{} {} {} 2024-01-02 03:04 bad\n", "cd".repeat(20), first, missing);
+    let (status, body) = server.request("POST", "update", format!("{}\n{}{}", first, log, line).as_bytes());
+    assert_eq!(status, 400);
+    assert!(body.contains("missing"));
+    assert_eq!(log_hashes(&a), vec![first]);
+}
@@ -0,0 +1,28 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn check_ignore_names_the_rule() {
+    let tmp = TempDir::new("check-ignore");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, ".svcignore", "*.log\n!keep.log\nbuild/\n");
+    write(&a, "sub/.svcignore", "!debug.log\n");
+    write(&a, "build/x.o", "");
+
+    let output = svc_ok(&a, &["check-ignore", "a.log", "keep.log", "build/x.o", "sub/debug.log", "src/main.rs", ".svc"]);
+    let lines: Vec<&str> = output.lines().collect();
+    assert_eq!(
+        lines,
+        vec![
+            "a.log: ignored by .svcignore:1:*.log",
+            "keep.log: included by .svcignore:2:!keep.log",
+            "build/x.o: ignored by .svcignore:3:build/",
+            "sub/debug.log: included by sub/.svcignore:1:!debug.log",
+            "src/main.rs: no rule matched",
+            ".svc: always ignored",
+        ]
+    );
+    svc_err(&a, &["check-ignore", "../outside"]);
+}
@@ -0,0 +1,165 @@
+mod common;
+
+use common::*;
+use std::{
+    fs,
+    os::unix::fs::{symlink, PermissionsExt},
+    path::Path,
+    process::{Command, Stdio},
+};
+
+// git without the user's configuration
+fn git_command(dir: &Path, args: &[&str]) -> Command {
+    let mut command = Command::new("git");
+    command
+        .args(args)
+        .current_dir(dir)
+        .env("GIT_CONFIG_NOSYSTEM", "1")
+        .env("HOME", dir)
+        .env("GIT_AUTHOR_NAME", "Jane Doe")
+        .env("GIT_AUTHOR_EMAIL", "jane@example.com")
+        .env("GIT_COMMITTER_NAME", "Jane Doe")
+        .env("GIT_COMMITTER_EMAIL", "jane@example.com");
+    command
+}
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    run_ok(git_command(dir, args))
+}
+
+fn has_git() -> bool {
+    Command::new("git").arg("--version").stdout(Stdio::null()).status().is_ok_and(|status| status.success())
+}
+
+fn git_commit(dir: &Path, message: &str, date: &str) {
+    git(dir, &["add", "-A"]);
+    let mut command = git_command(dir, &["commit", "-q", "-m", message]);
+    command.env("GIT_AUTHOR_DATE", date).env("GIT_COMMITTER_DATE", date);
+    run_ok(command);
+}
+
+// a history whose objects end up as deltas in a pack after "git gc"
+fn make_history(dir: &Path) {
+    fs::create_dir_all(dir).unwrap();
+    git(dir, &["init", "-q", "-b", "main"]);
+    let mut lines: Vec<String> = (0..300).map(|i| format!("line {} of a file big enough to be deltified", i)).collect();
+    write(dir, "big.txt", lines.join("\n"));
+    write(dir, "src/run.sh", "#!/bin/sh\necho run\n");
+    fs::set_permissions(dir.join("src/run.sh"), fs::Permissions::from_mode(0o755)).unwrap();
+    symlink("big.txt", dir.join("link")).unwrap();
+    git_commit(dir, "first", "2024-01-02T03:04:00+0200");
+    for i in 0..5 {
+        lines[i * 50] = format!("changed in commit {}", i);
+        write(dir, "big.txt", lines.join("\n"));
+        write(dir, &format!("src/file{}.txt", i), format!("file {}\n", i));
+        git_commit(dir, &format!("change {}\n\nfix C:\\new path", i), &format!("2024-02-0{}T10:00:00-0530", i + 1));
+    }
+    git(dir, &["tag", "-a", "v1", "-m", "release"]);
+    fs::remove_file(dir.join("src/file0.txt")).unwrap();
+    git_commit(dir, "remove a file", "2024-03-01T12:30:00+0000");
+}
+
+fn loose_objects(git_dir: &Path) -> usize {
+    fs::read_dir(git_dir.join("objects"))
+        .unwrap()
+        .map(|entry| entry.unwrap())
+        .filter(|entry| entry.file_name().len() == 2)
+        .map(|entry| fs::read_dir(entry.path()).unwrap().count())
+        .sum()
+}
+
+fn pack_index(git_dir: &Path) -> String {
+    fs::read_dir(git_dir.join("objects/pack"))
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .find(|path| path.extension().is_some_and(|ext| ext == "idx"))
+        .unwrap()
+        .to_str()
+        .unwrap()
+        .to_string()
+}
+
+#[test]
+fn import_packed_history_and_export_it_back() {
+    if !has_git() {
+        eprintln!("git is not installed, skipping");
+        return;
+    }
+    let tmp = TempDir::new("import-git");
+    let source = tmp.join("source");
+    make_history(&source);
+    git(&source, &["gc", "-q", "--aggressive", "--prune=now"]);
+    assert_eq!(loose_objects(&source.join(".git")), 0);
+    assert!(git(&source, &["verify-pack", "-v", &pack_index(&source.join(".git"))]).contains("chain length"));
+
+    let repo = tmp.join("repo");
+    init_repo(&repo);
+    svc_ok(&repo, &["import-git", source.to_str().unwrap()]);
+    assert_eq!(log_hashes(&repo).len(), 7);
+    assert_eq!(read(&repo, "big.txt"), read(&source, "big.txt"));
+    assert!(!repo.join("src/file0.txt").exists());
+    assert_eq!(read(&repo, "src/file4.txt"), "file 4\n");
+    assert_ne!(fs::metadata(repo.join("src/run.sh")).unwrap().permissions().mode() & 0o111, 0);
+    assert_eq!(fs::read_link(repo.join("link")).unwrap(), Path::new("big.txt"));
+    let log = svc_ok(&repo, &["log"]);
+    assert!(log.contains("fix C:\\new path"));
+    assert!(log.contains("Jane Doe <jane@example.com>"));
+    assert!(log.contains("2024-02-01 10:00-0530"));
+
+    // the same trees, authors and dates come back out
+    let export = tmp.join("export");
+    fs::create_dir_all(&export).unwrap();
+    git(&export, &["init", "-q"]);
+    let stream = svc_ok(&repo, &["export-git"]);
+    let mut child = git_command(&export, &["fast-import", "--quiet"]).stdin(Stdio::piped()).spawn().unwrap();
+    std::io::Write::write_all(child.stdin.as_mut().unwrap(), stream.as_bytes()).unwrap();
+    assert!(child.wait().unwrap().success());
+    let format = "--format=%T %an <%ae> %at %ai %B";
+    assert_eq!(git(&export, &["log", format, "master"]), git(&source, &["log", format, "main"]));
+}
+
+#[test]
+fn import_a_tag_of_a_bare_repo() {
+    if !has_git() {
+        eprintln!("git is not installed, skipping");
+        return;
+    }
+    let tmp = TempDir::new("import-git-bare");
+    let source = tmp.join("source");
+    make_history(&source);
+    git(tmp.path(), &["clone", "-q", "--bare", source.to_str().unwrap(), "bare.git"]);
+    let bare = tmp.join("bare.git");
+    git(&bare, &["gc", "-q", "--prune=now"]);
+    assert_eq!(loose_objects(&bare), 0);
+
+    let repo = tmp.join("repo");
+    init_repo(&repo);
+    svc_ok(&repo, &["import-git", bare.to_str().unwrap(), "-b", "v1"]);
+    assert_eq!(log_hashes(&repo).len(), 6);
+    assert_eq!(read(&repo, "src/file0.txt"), "file 0\n");
+
+    // only into a repo without commits
+    svc_err(&repo, &["import-git", bare.to_str().unwrap()]);
+}
+
+#[test]
+fn import_keeps_untracked_files() {
+    if !has_git() {
+        eprintln!("git is not installed, skipping");
+        return;
+    }
+    let tmp = TempDir::new("import-git-untracked");
+    let source = tmp.join("source");
+    make_history(&source);
+
+    let repo = tmp.join("repo");
+    init_repo(&repo);
+    write(&repo, "big.txt", "mine\n");
+    assert!(svc_err(&repo, &["import-git", source.to_str().unwrap()]).contains("untracked 'big.txt'"));
+    assert_eq!(read(&repo, "big.txt"), "mine\n");
+    assert!(log_hashes(&repo).is_empty());
+
+    fs::remove_file(repo.join("big.txt")).unwrap();
+    svc_ok(&repo, &["import-git", source.to_str().unwrap()]);
+    assert_eq!(log_hashes(&repo).len(), 7);
+}
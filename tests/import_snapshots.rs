@@ -0,0 +1,37 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn import_snapshots_in_date_order() {
+    let tmp = TempDir::new("import-snapshots");
+    let snaps = tmp.join("snaps");
+    write(&snaps, "backup-2021-03-05/f.txt", "two\n");
+    write(&snaps, "backup-2021-03-05/dir/g.txt", "g\n");
+    write(&snaps, "backup-2021-03-04/f.txt", "one\n");
+    write(&snaps, "backup-2021-03-06/f.txt", "two\n");
+    write(&snaps, "backup-2021-03-06/dir/g.txt", "g\n");
+    let sources = ["../snaps/backup-2021-03-06", "../snaps/backup-2021-03-04", "../snaps/backup-2021-03-05"];
+    let args = [&["import-snapshots", "--date-from-name"][..], &sources[..]].concat();
+
+    // files in the way are never overwritten
+    let repo = tmp.join("repo");
+    init_repo(&repo);
+    write(&repo, "f.txt", "mine\n");
+    assert!(svc_err(&repo, &args).contains("untracked 'f.txt'"));
+    assert_eq!(read(&repo, "f.txt"), "mine\n");
+    assert!(log_hashes(&repo).is_empty());
+
+    fs::remove_file(repo.join("f.txt")).unwrap();
+    let output = svc_ok(&repo, &args);
+    assert!(output.contains("snapshot backup-2021-03-06 is the same as the one before"));
+    let log = read(&repo, ".svc/log");
+    let dates: Vec<&str> = log.lines().filter_map(|line| line.split(' ').nth(3)).collect();
+    assert_eq!(dates, vec!["2021-03-04", "2021-03-05"]);
+    assert_eq!(read(&repo, "f.txt"), "two\n");
+    assert_eq!(read(&repo, "dir/g.txt"), "g\n");
+
+    // only into a repo without commits
+    svc_err(&repo, &args);
+}
@@ -0,0 +1,40 @@
+mod common;
+
+use common::*;
+use std::{fs, os::unix::fs::symlink, path::Path};
+
+#[test]
+fn links_are_committed_and_restored_as_links() {
+    let tmp = TempDir::new("links");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "target.txt", "target\n");
+    symlink("target.txt", a.join("link")).unwrap();
+    symlink("missing", a.join("dangling")).unwrap();
+    let first = commit(&a, "links");
+
+    fs::remove_file(a.join("link")).unwrap();
+    fs::remove_file(a.join("dangling")).unwrap();
+    commit(&a, "no links");
+    svc_ok(&a, &["checkout", &first]);
+    assert_eq!(fs::read_link(a.join("link")).unwrap(), Path::new("target.txt"));
+    assert_eq!(fs::read_link(a.join("dangling")).unwrap(), Path::new("missing"));
+}
+
+#[test]
+fn checkout_replaces_a_linked_directory_instead_of_following_it() {
+    let tmp = TempDir::new("linked-dir");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "dir/f.txt", "one\n");
+    let first = commit(&a, "dir");
+
+    let outside = tmp.join("outside");
+    write(&outside, "f.txt", "outside\n");
+    fs::remove_dir_all(a.join("dir")).unwrap();
+    symlink(&outside, a.join("dir")).unwrap();
+    svc_ok(&a, &["checkout", &first]);
+    assert!(!fs::symlink_metadata(a.join("dir")).unwrap().file_type().is_symlink());
+    assert_eq!(read(&a, "dir/f.txt"), "one\n");
+    assert_eq!(read(&outside, "f.txt"), "outside\n");
+}
@@ -0,0 +1,141 @@
+mod common;
+
+use common::*;
+use std::{fs, path::PathBuf};
+
+// a repo with a clone of it, both at the same commit
+fn repo_and_clone(tmp: &TempDir) -> (PathBuf, PathBuf) {
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "text.txt", "one\ntwo\nthree\n");
+    write(&a, "data.bin", b"\x00\x01");
+    write(&a, "gone.txt", "keep\n");
+    commit(&a, "base");
+    svc_ok(tmp.path(), &["clone", a.to_str().unwrap(), "b"]);
+    (a, tmp.join("b"))
+}
+
+#[test]
+fn merge_without_conflicts() {
+    let tmp = TempDir::new("merge-clean");
+    let (a, b) = repo_and_clone(&tmp);
+    write(&a, "text.txt", "ONE\ntwo\nthree\n");
+    let theirs = commit(&a, "upstream");
+    write(&b, "text.txt", "one\ntwo\nTHREE\n");
+    let ours = commit(&b, "local");
+
+    svc_ok(&b, &["pull"]);
+    assert_eq!(read(&b, "text.txt"), "ONE\ntwo\nTHREE\n");
+    let log = read(&b, ".svc/log");
+    let merge = log.lines().last().unwrap();
+    assert!(merge.contains(&format!(" {},{} ", ours, theirs)));
+    assert!(!b.join(".svc/merge_head").exists());
+}
+
+#[test]
+fn merge_with_conflicts_of_every_kind() {
+    let tmp = TempDir::new("merge-conflicts");
+    let (a, b) = repo_and_clone(&tmp);
+    write(&a, "text.txt", "one\nupstream\nthree\n");
+    write(&a, "data.bin", b"\x00\x02");
+    write(&a, "gone.txt", "changed\n");
+    let theirs = commit(&a, "upstream");
+    write(&b, "text.txt", "one\nlocal\nthree\n");
+    write(&b, "data.bin", b"\x00\x03");
+    fs::remove_file(b.join("gone.txt")).unwrap();
+    let ours = commit(&b, "local");
+
+    let output = svc_ok(&b, &["pull"]);
+    assert!(output.contains("conflict (text): text.txt"));
+    assert!(output.contains("conflict (binary): data.bin"));
+    assert!(output.contains("conflict (deleted): gone.txt"));
+    assert!(read(&b, "text.txt").contains("<<<<<<< "));
+    // the changed side is kept
+    assert_eq!(read(&b, "gone.txt"), "changed\n");
+    assert_eq!(head(&b), ours);
+
+    let status = svc_ok(&b, &["status"]);
+    assert!(status.contains("data.bin (binary)") && status.contains("gone.txt (deleted)"));
+    svc_err(&b, &["checkout", &ours]);
+    let errors = svc_err(&b, &["commit", "-m", "merged"]);
+    assert!(errors.contains("'text.txt' still has conflict markers"));
+    assert!(errors.contains("'data.bin' has a binary conflict"));
+    assert!(errors.contains("'gone.txt' has a deleted conflict"));
+
+    // nothing is resolved when one of the paths can't be
+    svc_err(&b, &["resolve", "data.bin", "text.txt"]);
+    svc_err(&b, &["resolve", "nothing.txt"]);
+    svc_ok(&b, &["resolve", "--theirs", "data.bin"]);
+    assert_eq!(fs::read(b.join("data.bin")).unwrap(), b"\x00\x02");
+    svc_ok(&b, &["resolve", "--ours", "gone.txt"]);
+    assert!(!b.join("gone.txt").exists());
+    svc_err(&b, &["resolve", "data.bin"]);
+
+    // a text conflict is resolved by removing the markers
+    let errors = svc_err(&b, &["commit", "-m", "merged"]);
+    assert!(errors.contains("text.txt") && !errors.contains("data.bin"));
+    write(&b, "text.txt", "one\nboth\nthree\n");
+    let merged = commit(&b, "merged");
+    assert_ne!(merged, ours);
+    assert!(read(&b, ".svc/log").contains(&format!(" {},{} ", ours, theirs)));
+    assert!(!b.join(".svc/merge_head").exists());
+
+    svc_ok(&b, &["checkout", &ours]);
+    assert!(b.join("text.txt").exists());
+}
+
+#[test]
+fn fast_forward_keeps_untracked_files() {
+    let tmp = TempDir::new("merge-untracked");
+    let (a, b) = repo_and_clone(&tmp);
+    write(&a, "new.txt", "upstream\n");
+    let theirs = commit(&a, "add new");
+
+    write(&b, "new.txt", "mine\n");
+    assert!(svc_err(&b, &["pull"]).contains("untracked 'new.txt'"));
+    assert_eq!(read(&b, "new.txt"), "mine\n");
+
+    fs::remove_file(b.join("new.txt")).unwrap();
+    svc_ok(&b, &["pull"]);
+    assert_eq!(head(&b), theirs);
+    assert_eq!(read(&b, "new.txt"), "upstream\n");
+}
+
+#[test]
+fn fast_forward_from_an_old_head_drops_the_later_commits() {
+    let tmp = TempDir::new("merge-old-head");
+    let (a, b) = repo_and_clone(&tmp);
+    let base = head(&b);
+    write(&b, "text.txt", "local\n");
+    let dropped = commit(&b, "local");
+    svc_ok(&b, &["checkout", &base]);
+    write(&a, "new.txt", "upstream\n");
+    let theirs = commit(&a, "upstream");
+
+    // like a commit made at HEAD would
+    svc_ok(&b, &["pull"]);
+    assert_eq!(head(&b), theirs);
+    assert_eq!(log_hashes(&b), vec![base, theirs]);
+    assert!(read(&b, ".svc/log.bak").contains(&dropped));
+    assert_eq!(read(&b, "new.txt"), "upstream\n");
+}
+
+#[test]
+fn revert_conflicts_are_resolved_the_same_way() {
+    let tmp = TempDir::new("revert-conflicts");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "base");
+    write(&a, "f.txt", "two\n");
+    let target = commit(&a, "two");
+    write(&a, "f.txt", "three\n");
+    commit(&a, "three");
+
+    let output = svc_ok(&a, &["revert", &target]);
+    assert!(output.contains("conflict (text): f.txt"));
+    svc_ok(&a, &["resolve", "--theirs", "f.txt"]);
+    assert_eq!(read(&a, "f.txt"), "one\n");
+    svc_ok(&a, &["commit", "-m", "reverted"]);
+    assert!(!a.join(".svc/revert_head").exists());
+}
@@ -0,0 +1,58 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn inspect_objects() {
+    let tmp = TempDir::new("plumbing");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/g.txt", "g\n");
+    commit(&a, "first");
+
+    let hash = svc_ok(&a, &["hash-object", "f.txt"]).trim().to_string();
+    let listing = svc_ok(&a, &["ls-tree", "HEAD"]);
+    let lines: Vec<&str> = listing.lines().collect();
+    assert_eq!(lines.len(), 2);
+    assert!(lines.contains(&format!("644 blob {}        4\tf.txt", hash).as_str()));
+    let tree = lines.iter().find(|line| line.contains("\tdir")).unwrap().split(' ').nth(2).unwrap().to_string();
+    assert_eq!(svc_ok(&a, &["ls-tree", "HEAD", "dir"]), svc_ok(&a, &["ls-tree", &tree]).replace("\tg.txt", "\tdir/g.txt"));
+    assert!(svc_ok(&a, &["ls-tree", "-r", "HEAD"]).contains("\tdir/g.txt"));
+    svc_err(&a, &["ls-tree", "HEAD", "missing"]);
+
+    assert_eq!(svc_ok(&a, &["cat-object", &hash]), "one\n");
+    assert_eq!(svc_ok(&a, &["cat-object", "-t", &hash[..8]]), "blob\n");
+    assert_eq!(svc_ok(&a, &["cat-object", "-s", &hash]), "4\n");
+    assert_eq!(svc_ok(&a, &["cat-object", "-t", &tree]), "tree\n");
+    assert!(svc_ok(&a, &["cat-object", "-p", &tree]).contains("\tg.txt"));
+
+    // only written when asked
+    write(&a, "new.txt", "new\n");
+    let new = svc_ok(&a, &["hash-object", "new.txt"]).trim().to_string();
+    svc_err(&a, &["cat-object", &new]);
+    assert_eq!(svc_ok(&a, &["hash-object", "-w", "new.txt"]).trim(), new);
+    assert_eq!(svc_ok(&a, &["cat-object", &new]), "new\n");
+}
+
+#[test]
+fn corrupted_trees_are_errors() {
+    let tmp = TempDir::new("plumbing-corrupt");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "dir/g.txt", "g\n");
+    commit(&a, "first");
+    let listing = svc_ok(&a, &["ls-tree", "HEAD"]);
+    let tree = listing.split(' ').nth(2).unwrap();
+
+    let path = a.join(".svc/objects").join(&tree[..2]).join(&tree[2..]);
+    fs::write(&path, format!("{} 2 3\n{} blob 2 999 g.txt\n", tree, tree)).unwrap();
+    for args in [&["ls-tree", "-r", "HEAD"][..], &["cat-object", "-p", tree], &["export", "HEAD", "../out"]] {
+        let errors = svc_err(&a, args);
+        assert!(errors.contains("tree is corrupted") && !errors.contains("panicked"), "{:?}: {}", args, errors);
+    }
+    assert!(svc_ok(&a, &["cat-object", tree]).contains("999"));
+    fs::remove_file(&path).unwrap();
+    assert!(!svc_err(&a, &["ls-tree", "HEAD", "dir"]).contains("panicked"));
+}
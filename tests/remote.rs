@@ -0,0 +1,146 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn push_and_pull_a_local_path() {
+    let tmp = TempDir::new("local-remote");
+    let (a, b) = (tmp.join("a"), tmp.join("b"));
+    init_repo(&a);
+    init_repo(&b);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+
+    // a plain path is relative to the workspace root
+    svc_ok(&a, &["set-remote", "../b"]);
+    svc_ok(&a, &["push"]);
+    assert_eq!(log_hashes(&b), log_hashes(&a));
+    assert_eq!(head(&b), head(&a));
+    assert_eq!(read(&b, "f.txt"), "one\n");
+
+    svc_ok(&b, &["set-remote", &format!("file://{}", a.display())]);
+    write(&b, "f.txt", "two\n");
+    let second = commit(&b, "second");
+    svc_ok(&b, &["push"]);
+    assert_eq!(head(&a), second);
+    assert_eq!(read(&a, "f.txt"), "two\n");
+
+    write(&a, "f.txt", "three\n");
+    let third = commit(&a, "third");
+    svc_ok(&b, &["pull"]);
+    assert_eq!(head(&b), third);
+    assert_eq!(read(&b, "f.txt"), "three\n");
+
+    // a workspace with unsaved changes isn't moved by a push
+    write(&a, "f.txt", "unsaved\n");
+    write(&b, "g.txt", "new\n");
+    commit(&b, "fourth");
+    assert!(svc_err(&b, &["push"]).contains("remote workspace can't be updated"));
+    assert_eq!(head(&a), third);
+    assert_eq!(read(&a, "f.txt"), "unsaved\n");
+}
+
+#[test]
+fn diverged_push_is_refused() {
+    let tmp = TempDir::new("local-diverged");
+    let (a, b) = (tmp.join("a"), tmp.join("b"));
+    init_repo(&a);
+    init_repo(&b);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    svc_ok(&a, &["set-remote", "../b"]);
+    svc_ok(&a, &["push"]);
+
+    write(&b, "b.txt", "b\n");
+    commit(&b, "from b");
+    write(&a, "a.txt", "a\n");
+    let ours = commit(&a, "from a");
+    assert!(svc_err(&a, &["push"]).contains("diverged"));
+    assert!(!log_hashes(&b).contains(&ours));
+}
+
+#[test]
+fn old_remote_url_becomes_origin() {
+    let tmp = TempDir::new("remote-url");
+    let (a, b) = (tmp.join("a"), tmp.join("b"));
+    init_repo(&a);
+    init_repo(&b);
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+    // .svc/repo as written before named remotes
+    write(&a, ".svc/repo", "repo_name = a\nrepo_intro = \nremote_url = ../b\ncreated_at = 2024-01-02 03:04\nupdated_at = 2024-01-02 03:04\n");
+
+    assert_eq!(svc_ok(&a, &["remote", "list"]), "origin\t../b (default)\n");
+    svc_ok(&a, &["push"]);
+    assert_eq!(head(&b), first);
+
+    // the next change writes it out as a named remote
+    svc_ok(&a, &["remote", "add", "backup", "../c"]);
+    let meta = read(&a, ".svc/repo");
+    assert!(meta.contains("remote.origin = ../b") && meta.contains("remote.backup = ../c"));
+    assert!(!meta.contains("remote_url"));
+}
+
+#[test]
+fn manage_named_remotes() {
+    let tmp = TempDir::new("named-remotes");
+    let (a, b, c) = (tmp.join("a"), tmp.join("b"), tmp.join("c"));
+    for dir in [&a, &b, &c] {
+        init_repo(dir);
+    }
+    write(&a, "f.txt", "one\n");
+    let first = commit(&a, "first");
+    svc_ok(&a, &["remote", "add", "origin", "../b"]);
+    svc_ok(&a, &["remote", "add", "backup", "../c"]);
+    svc_err(&a, &["remote", "add", "backup", "../c"]);
+    svc_err(&a, &["remote", "add", "bad name", "../c"]);
+
+    svc_ok(&a, &["push", "backup"]);
+    assert_eq!(head(&c), first);
+    assert!(head(&b).is_empty());
+    svc_err(&a, &["push", "nowhere"]);
+
+    svc_ok(&a, &["remote", "rename", "backup", "mirror"]);
+    svc_ok(&a, &["remote", "set-default", "mirror"]);
+    assert_eq!(svc_ok(&a, &["remote", "list"]), "origin\t../b\nmirror\t../c (default)\n");
+    svc_ok(&a, &["remote", "set-url", "mirror", "../b"]);
+    svc_ok(&a, &["push"]);
+    assert_eq!(head(&b), first);
+
+    // removing the default falls back to another remote
+    svc_ok(&a, &["remote", "remove", "mirror"]);
+    assert_eq!(svc_ok(&a, &["remote", "list"]), "origin\t../b (default)\n");
+}
+
+#[test]
+fn ahead_and_behind_the_default_remote() {
+    let tmp = TempDir::new("ahead-behind");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    commit(&a, "first");
+    svc_ok(tmp.path(), &["clone", "a", "b"]);
+    let b = tmp.join("b");
+    assert!(svc_ok(&b, &["status"]).contains("up to date with 'origin'."));
+
+    write(&b, "f.txt", "two\n");
+    commit(&b, "second");
+    write(&b, "f.txt", "three\n");
+    let pushed = commit(&b, "third");
+    assert!(svc_ok(&b, &["status"]).contains("ahead of 'origin' by 2 commit(s)."));
+    svc_ok(&b, &["push"]);
+    assert!(svc_ok(&b, &["info"]).contains("up to date with 'origin'."));
+
+    // the remote's state is only learned by talking to it
+    write(&a, "g.txt", "upstream\n");
+    commit(&a, "upstream");
+    assert!(svc_ok(&b, &["status"]).contains("up to date with 'origin'."));
+    svc_ok(&b, &["fetch"]);
+    assert!(svc_ok(&b, &["status"]).contains("behind 'origin' by 1 commit(s)."));
+    assert_eq!(head(&b), pushed);
+    assert!(!b.join("g.txt").exists());
+
+    write(&b, "h.txt", "local\n");
+    commit(&b, "local");
+    assert!(svc_ok(&b, &["status"]).contains("diverged from 'origin': ahead by 1, behind by 1 commit(s)."));
+}
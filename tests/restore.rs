@@ -0,0 +1,52 @@
+mod common;
+
+use common::*;
+use std::{fs, os::unix::fs::symlink};
+
+#[test]
+fn restore_paths_from_a_version() {
+    let tmp = TempDir::new("restore");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/g.txt", "g1\n");
+    write(&a, "dir/h.txt", "h\n");
+    let first = commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    write(&a, "dir/g.txt", "g2\n");
+    fs::remove_file(a.join("dir/h.txt")).unwrap();
+    write(&a, "dir/new.txt", "new\n");
+    let second = commit(&a, "second");
+
+    // HEAD by default, discarding unsaved changes
+    write(&a, "f.txt", "unsaved\n");
+    svc_ok(&a, &["restore", "f.txt"]);
+    assert_eq!(read(&a, "f.txt"), "two\n");
+
+    // a directory as it was, without the files HEAD added to it
+    svc_ok(&a, &["restore", "-s", &first, "dir"]);
+    assert_eq!(read(&a, "dir/g.txt"), "g1\n");
+    assert_eq!(read(&a, "dir/h.txt"), "h\n");
+    assert!(!a.join("dir/new.txt").exists());
+    assert_eq!(read(&a, "f.txt"), "two\n");
+    assert_eq!(head(&a), second);
+
+    svc_err(&a, &["restore", "-s", &first, "missing.txt"]);
+    svc_err(&a, &["restore", "../outside.txt"]);
+}
+
+#[test]
+fn restore_refuses_a_parent_linked_outside() {
+    let tmp = TempDir::new("restore-link");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "dir/g.txt", "g\n");
+    commit(&a, "first");
+
+    let outside = tmp.join("outside");
+    write(&outside, "g.txt", "outside\n");
+    fs::remove_dir_all(a.join("dir")).unwrap();
+    symlink(&outside, a.join("dir")).unwrap();
+    assert!(svc_err(&a, &["restore", "dir/g.txt"]).contains("outside the workspace"));
+    assert_eq!(read(&outside, "g.txt"), "outside\n");
+}
@@ -0,0 +1,32 @@
+mod common;
+
+use common::*;
+
+#[test]
+fn show_versions_and_their_files() {
+    let tmp = TempDir::new("show");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\n");
+    write(&a, "dir/g.txt", "g\n");
+    let first = commit(&a, "first");
+    write(&a, "f.txt", "two\n");
+    write(&a, "h.txt", "h\n");
+    write(&a, "empty/.keep", "");
+    std::fs::remove_file(a.join("dir/g.txt")).unwrap();
+    let second = commit(&a, "second\n\nwith a body");
+
+    assert_eq!(svc_ok(&a, &["cat", "f.txt"]), "two\n");
+    assert_eq!(svc_ok(&a, &["cat", "f.txt", "-v", &first]), "one\n");
+    assert_eq!(svc_ok(&a, &["show", &format!("{}:dir/g.txt", first)]), "g\n");
+    assert_eq!(svc_ok(&a.join("dir"), &["show", &format!("{}:../f.txt", first)]), "one\n");
+    svc_err(&a, &["cat", "dir/g.txt"]);
+    svc_err(&a, &["cat", "dir", "-v", &first]);
+
+    let show = svc_ok(&a, &["show", &second]);
+    assert!(show.contains(&format!("commit {}", second)));
+    assert!(show.contains("\twith a body"));
+    let changes: Vec<&str> = show.lines().filter(|line| line.len() > 3 && &line[1..3] == "  ").collect();
+    assert_eq!(changes, vec!["D  dir/g.txt", "A  empty/.keep", "M  f.txt", "A  h.txt"]);
+    svc_err(&a, &["show", "0000"]);
+}
@@ -0,0 +1,63 @@
+mod common;
+
+use common::*;
+use std::fs;
+
+#[test]
+fn stash_push_and_pop() {
+    let tmp = TempDir::new("stash");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\ntwo\nthree\n");
+    write(&a, "g.txt", "g\n");
+    let first = commit(&a, "first");
+
+    write(&a, "f.txt", "ONE\ntwo\nthree\n");
+    fs::remove_file(a.join("g.txt")).unwrap();
+    write(&a, "u.txt", "untracked\n");
+    svc_ok(&a, &["stash", "push", "-m", "wip"]);
+    assert_eq!(read(&a, "f.txt"), "one\ntwo\nthree\n");
+    assert_eq!(read(&a, "g.txt"), "g\n");
+    assert_eq!(read(&a, "u.txt"), "untracked\n");
+    assert!(svc_ok(&a, &["stash", "list"]).contains("stash@{0}: wip"));
+    assert_eq!(head(&a), first);
+
+    // local changes are merged with the entry, not lost
+    write(&a, "f.txt", "one\ntwo\nTHREE\n");
+    svc_ok(&a, &["stash", "pop"]);
+    assert_eq!(read(&a, "f.txt"), "ONE\ntwo\nTHREE\n");
+    assert!(!a.join("g.txt").exists());
+    assert_eq!(svc_ok(&a, &["stash", "list"]), "");
+
+    // untracked files only with -u, and never over a file in the way
+    svc_ok(&a, &["stash", "push", "-u"]);
+    assert!(!a.join("u.txt").exists());
+    write(&a, "u.txt", "in the way\n");
+    assert!(svc_err(&a, &["stash", "pop"]).contains("untracked 'u.txt'"));
+    fs::remove_file(a.join("u.txt")).unwrap();
+    svc_ok(&a, &["stash", "pop"]);
+    assert_eq!(read(&a, "u.txt"), "untracked\n");
+}
+
+#[test]
+fn stash_pop_with_conflicts_keeps_the_entry() {
+    let tmp = TempDir::new("stash-conflict");
+    let a = tmp.join("a");
+    init_repo(&a);
+    write(&a, "f.txt", "one\ntwo\nthree\n");
+    commit(&a, "first");
+    write(&a, "f.txt", "stashed\ntwo\nthree\n");
+    svc_ok(&a, &["stash", "push"]);
+
+    write(&a, "f.txt", "local\ntwo\nthree\n");
+    let output = svc_ok(&a, &["stash", "pop"]);
+    assert!(output.contains("conflict (text): f.txt"));
+    let merged = read(&a, "f.txt");
+    assert!(merged.contains("<<<<<<< ") && merged.contains("local") && merged.contains("stashed"));
+    assert!(svc_ok(&a, &["stash", "list"]).contains("stash@{0}"));
+
+    write(&a, "f.txt", "both\ntwo\nthree\n");
+    svc_ok(&a, &["stash", "drop"]);
+    assert_eq!(svc_ok(&a, &["stash", "list"]), "");
+    svc_err(&a, &["stash", "drop"]);
+}